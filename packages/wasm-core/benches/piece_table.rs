@@ -0,0 +1,61 @@
+//! Timings for piece-table workloads, run with `cargo bench`
+//! Reports wall-clock times only; correctness is covered by the unit tests
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use teppan_wasm_core::PieceTable;
+
+/// Best of several runs, to smooth out scheduler noise
+fn time<T>(runs: usize, mut f: impl FnMut() -> T) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let started = Instant::now();
+            black_box(f());
+            started.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+fn build_lines(lines: usize, prepend: bool) -> PieceTable {
+    let mut pt = PieceTable::new(String::new());
+    for i in 0..lines {
+        let offset = if prepend { 0 } else { pt.get_length() };
+        pt.insert(offset, &format!("line {}\n", i));
+    }
+    pt
+}
+
+fn bench_prepend_100k_lines() {
+    let lines = 100_000;
+    let append = time(3, || build_lines(lines, false));
+    let prepend = time(3, || build_lines(lines, true));
+    println!("prepend {} lines: {:?} (append: {:?})", lines, prepend, append);
+}
+
+fn bench_line_lengths_against_per_line() {
+    let mut pt = PieceTable::new(String::new());
+    for i in 0..20_000 {
+        let offset = if i % 3 == 0 { 0 } else { pt.get_length() };
+        pt.insert(offset, &format!("l\u{ed}ne {}\n", "x".repeat(i % 17)));
+    }
+
+    let single_pass = time(5, || pt.line_lengths());
+    let per_line = time(1, || {
+        (0..pt.get_line_count())
+            .map(|line| pt.line_char_length(line))
+            .collect::<Vec<_>>()
+    });
+    println!(
+        "lineLengths over {} lines: {:?} (per-line calls: {:?})",
+        pt.get_line_count(),
+        single_pass,
+        per_line
+    );
+}
+
+fn main() {
+    bench_prepend_100k_lines();
+    bench_line_lengths_against_per_line();
+}
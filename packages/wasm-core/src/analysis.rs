@@ -0,0 +1,198 @@
+use serde::Serialize;
+
+/// Summary of content characteristics used for open-time decisions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextCharacteristics {
+    /// False if the content contains NUL or is dominated by control characters
+    pub is_text: bool,
+    /// Whether the content starts with a U+FEFF byte order mark
+    pub has_bom: bool,
+    /// Whether any line ends with "\r\n"
+    pub has_crlf: bool,
+    /// Whether the content contains tab characters
+    pub has_tabs: bool,
+}
+
+/// Line-ending style of a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+    /// The content has no line breaks
+    #[default]
+    None,
+}
+
+/// Load-time report of encoding and line-ending anomalies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentReport {
+    /// Whether the content is valid UTF-8; always true for text held by a document
+    pub valid_utf8: bool,
+    /// The most common line ending, preferring LF, then CRLF, on a tie
+    pub line_ending: LineEnding,
+    /// Whether more than one line-ending style occurs
+    pub mixed_line_endings: bool,
+    /// Whether the content starts with a U+FEFF byte order mark
+    pub has_bom: bool,
+    /// Whether the last line ends with a line break
+    pub has_final_newline: bool,
+}
+
+/// Control characters other than common whitespace suggest binary content
+fn is_suspicious_control(c: char) -> bool {
+    c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c')
+}
+
+/// Scan content once, chunk by chunk, and summarize its characteristics
+pub fn characteristics<'a>(chunks: impl IntoIterator<Item = &'a str>) -> TextCharacteristics {
+    let mut result = TextCharacteristics::default();
+    let mut has_nul = false;
+    let mut total_chars = 0;
+    let mut control_chars = 0;
+    let mut previous = None;
+
+    for c in chunks.into_iter().flat_map(str::chars) {
+        if previous.is_none() && c == '\u{feff}' {
+            result.has_bom = true;
+        }
+        match c {
+            '\0' => has_nul = true,
+            '\t' => result.has_tabs = true,
+            '\n' if previous == Some('\r') => result.has_crlf = true,
+            _ => {}
+        }
+        if is_suspicious_control(c) {
+            control_chars += 1;
+        }
+        total_chars += 1;
+        previous = Some(c);
+    }
+
+    // Allow the occasional stray control character (form feeds, escapes) in text
+    result.is_text = !has_nul && control_chars * 10 <= total_chars;
+    result
+}
+
+/// Scan content once, chunk by chunk, and report its line endings and BOM
+/// A "\r\n" split across two chunks still counts as one CRLF
+pub fn report<'a>(chunks: impl IntoIterator<Item = &'a str>) -> ContentReport {
+    let (mut lf, mut crlf, mut cr) = (0usize, 0usize, 0usize);
+    let mut first = None;
+    let mut previous = None;
+
+    for c in chunks.into_iter().flat_map(str::chars) {
+        first.get_or_insert(c);
+        match (previous, c) {
+            (Some('\r'), '\n') => crlf += 1,
+            (_, '\n') => lf += 1,
+            (Some('\r'), _) => cr += 1,
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    if previous == Some('\r') {
+        cr += 1;
+    }
+
+    let line_ending = if lf + crlf + cr == 0 {
+        LineEnding::None
+    } else if lf >= crlf && lf >= cr {
+        LineEnding::Lf
+    } else if crlf >= cr {
+        LineEnding::Crlf
+    } else {
+        LineEnding::Cr
+    };
+
+    ContentReport {
+        valid_utf8: true,
+        line_ending,
+        mixed_line_endings: [lf, crlf, cr].iter().filter(|&&count| count > 0).count() > 1,
+        has_bom: first == Some('\u{feff}'),
+        has_final_newline: matches!(previous, Some('\n' | '\r')),
+    }
+}
+
+/// Report on raw bytes, such as a file read before decoding
+/// Invalid UTF-8 is reported with the remaining fields computed on the lossy decoding
+pub fn report_bytes(bytes: &[u8]) -> ContentReport {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => report([text]),
+        Err(_) => ContentReport {
+            valid_utf8: false,
+            ..report([String::from_utf8_lossy(bytes).as_ref()])
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crlf_bom_and_tabs() {
+        let result = characteristics(["\u{feff}a\tb\r", "\nc\r\n"]);
+        assert_eq!(
+            result,
+            TextCharacteristics {
+                is_text: true,
+                has_bom: true,
+                has_crlf: true,
+                has_tabs: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_plain_and_binary_content() {
+        let plain = characteristics(["line\nline\n"]);
+        assert!(plain.is_text && !plain.has_bom && !plain.has_crlf && !plain.has_tabs);
+
+        assert!(!characteristics(["PK\u{3}\u{4}\0\0"]).is_text);
+        assert!(characteristics([""]).is_text);
+    }
+
+    #[test]
+    fn test_report_bom_and_mixed_endings() {
+        let result = report(["\u{feff}one\r", "\ntwo\r\nthree\nfour\r\n"]);
+        assert_eq!(
+            result,
+            ContentReport {
+                valid_utf8: true,
+                line_ending: LineEnding::Crlf,
+                mixed_line_endings: true,
+                has_bom: true,
+                has_final_newline: true,
+            }
+        );
+        assert_eq!(report(["a\rb\rc\n"]).line_ending, LineEnding::Cr);
+    }
+
+    #[test]
+    fn test_report_clean_lf() {
+        let result = report(["one\ntwo\n", "three"]);
+        assert_eq!(
+            result,
+            ContentReport {
+                valid_utf8: true,
+                line_ending: LineEnding::Lf,
+                mixed_line_endings: false,
+                has_bom: false,
+                has_final_newline: false,
+            }
+        );
+        assert_eq!(report([""]).line_ending, LineEnding::None);
+    }
+
+    #[test]
+    fn test_report_invalid_utf8_bytes() {
+        let result = report_bytes(b"ok\n\xff\xfe\n");
+        assert!(!result.valid_utf8);
+        assert_eq!(result.line_ending, LineEnding::Lf);
+        assert!(report_bytes(b"ok\n").valid_utf8);
+    }
+}
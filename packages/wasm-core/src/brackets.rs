@@ -0,0 +1,220 @@
+/// Classification of the token at an offset, as reported by a host highlighter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Code,
+    String,
+    Comment,
+}
+
+impl TokenKind {
+    /// Parse a classifier result; anything unrecognized counts as code
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "string" => TokenKind::String,
+            "comment" => TokenKind::Comment,
+            _ => TokenKind::Code,
+        }
+    }
+}
+
+/// Bracket pairs recognized by the bracket helpers
+const BRACKET_PAIRS: [(u8, u8); 3] = [(b'(', b')'), (b'[', b']'), (b'{', b'}')];
+
+fn closing_for(open: u8) -> Option<u8> {
+    BRACKET_PAIRS
+        .iter()
+        .find(|(o, _)| *o == open)
+        .map(|(_, c)| *c)
+}
+
+fn opening_for(close: u8) -> Option<u8> {
+    BRACKET_PAIRS
+        .iter()
+        .find(|(_, c)| *c == close)
+        .map(|(o, _)| *o)
+}
+
+/// Find the innermost balanced bracket pair enclosing an offset
+/// Only brackets for which `is_code` returns true are considered, so brackets
+/// inside strings or comments can be skipped. Returns the offsets of the opening
+/// and closing brackets, or None if the offset is at top level or the
+/// surrounding brackets are unbalanced
+pub fn enclosing_pair(text: &str, offset: usize, is_code: impl Fn(usize) -> bool) -> Option<(usize, usize)> {
+    let bytes = text.as_bytes();
+    if offset > bytes.len() {
+        return None;
+    }
+
+    // Scan backward for the first opener that isn't closed before the offset
+    let mut pending_closers = Vec::new();
+    let mut open_index = None;
+    for i in (0..offset).rev() {
+        let byte = bytes[i];
+        if (opening_for(byte).is_some() || closing_for(byte).is_some()) && !is_code(i) {
+            continue;
+        }
+        if opening_for(byte).is_some() {
+            pending_closers.push(byte);
+        } else if let Some(close) = closing_for(byte) {
+            match pending_closers.pop() {
+                Some(expected) if expected == close => {}
+                Some(_) => return None,
+                None => {
+                    open_index = Some(i);
+                    break;
+                }
+            }
+        }
+    }
+
+    let open_index = open_index?;
+    let expected_close = closing_for(bytes[open_index])?;
+
+    // Scan forward for the closer that matches the opener
+    let mut pending_openers = Vec::new();
+    for (i, &byte) in bytes.iter().enumerate().skip(offset) {
+        if (opening_for(byte).is_some() || closing_for(byte).is_some()) && !is_code(i) {
+            continue;
+        }
+        if closing_for(byte).is_some() {
+            pending_openers.push(byte);
+        } else if let Some(open) = opening_for(byte) {
+            match pending_openers.pop() {
+                Some(expected) if expected == open => {}
+                Some(_) => return None,
+                None if byte == expected_close => return Some((open_index, i)),
+                None => return None,
+            }
+        }
+    }
+
+    None
+}
+
+/// Find the bracket matching the one at or just before an offset
+/// The byte at `offset` is tried first, then the byte before it. Scanning
+/// respects nesting, skips brackets for which `is_code` returns false and stops
+/// at the ends of the text. Returns None if no bracket is adjacent or the
+/// bracket is unmatched
+pub fn matching_bracket(text: &str, offset: usize, is_code: impl Fn(usize) -> bool) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let is_bracket = |i: usize| {
+        bytes
+            .get(i)
+            .is_some_and(|&b| (opening_for(b).is_some() || closing_for(b).is_some()) && is_code(i))
+    };
+    let index = if is_bracket(offset) {
+        offset
+    } else if offset > 0 && is_bracket(offset - 1) {
+        offset - 1
+    } else {
+        return None;
+    };
+
+    let bracket = bytes[index];
+    let mut pending = Vec::new();
+    if let Some(close) = closing_for(bracket) {
+        for (i, &byte) in bytes.iter().enumerate().skip(index + 1) {
+            if !is_bracket(i) {
+                continue;
+            }
+            if let Some(expected) = closing_for(byte) {
+                pending.push(expected);
+            } else {
+                match pending.pop() {
+                    Some(expected) if expected == byte => {}
+                    Some(_) => return None,
+                    None => return (byte == close).then_some(i),
+                }
+            }
+        }
+    } else {
+        let open = opening_for(bracket)?;
+        for i in (0..index).rev() {
+            if !is_bracket(i) {
+                continue;
+            }
+            let byte = bytes[i];
+            if let Some(expected) = opening_for(byte) {
+                pending.push(expected);
+            } else {
+                match pending.pop() {
+                    Some(expected) if expected == byte => {}
+                    Some(_) => return None,
+                    None => return (byte == open).then_some(i),
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enclosing_pair_nested() {
+        let text = "f(a, [b, {c}])";
+        assert_eq!(enclosing_pair(text, 10, |_| true), Some((9, 11)));
+        assert_eq!(enclosing_pair(text, 7, |_| true), Some((5, 12)));
+        assert_eq!(enclosing_pair(text, 3, |_| true), Some((1, 13)));
+    }
+
+    #[test]
+    fn test_enclosing_pair_top_level() {
+        assert_eq!(enclosing_pair("(a) b (c)", 4, |_| true), None);
+        assert_eq!(enclosing_pair("", 0, |_| true), None);
+    }
+
+    #[test]
+    fn test_enclosing_pair_skips_non_code() {
+        let text = "{ s = \"}\"; x }";
+        let in_string = |i: usize| (6..9).contains(&i);
+        assert_eq!(enclosing_pair(text, 12, |_| true), None);
+        assert_eq!(enclosing_pair(text, 12, |i| !in_string(i)), Some((0, 13)));
+    }
+
+    #[test]
+    fn test_token_kind_parse() {
+        assert_eq!(TokenKind::parse("string"), TokenKind::String);
+        assert_eq!(TokenKind::parse("comment"), TokenKind::Comment);
+        assert_eq!(TokenKind::parse("keyword"), TokenKind::Code);
+    }
+
+    #[test]
+    fn test_enclosing_pair_unbalanced() {
+        assert_eq!(enclosing_pair("(a]", 2, |_| true), None);
+        assert_eq!(enclosing_pair("(a", 2, |_| true), None);
+        assert_eq!(enclosing_pair("[(a]", 3, |_| true), None);
+    }
+
+    #[test]
+    fn test_matching_bracket_nested() {
+        let text = "(a[b]c)";
+        assert_eq!(matching_bracket(text, 0, |_| true), Some(6));
+        assert_eq!(matching_bracket(text, 7, |_| true), Some(0));
+        assert_eq!(matching_bracket(text, 2, |_| true), Some(4));
+        assert_eq!(matching_bracket(text, 5, |_| true), Some(2));
+        assert_eq!(matching_bracket(text, 1, |_| true), Some(6));
+        assert_eq!(matching_bracket("(ab)", 2, |_| true), None);
+    }
+
+    #[test]
+    fn test_matching_bracket_unbalanced() {
+        let text = "(a[b)";
+        assert_eq!(matching_bracket(text, 0, |_| true), None);
+        assert_eq!(matching_bracket(text, 4, |_| true), None);
+        assert_eq!(matching_bracket(text, 2, |_| true), None);
+        assert_eq!(matching_bracket("((a)", 0, |_| true), None);
+        assert_eq!(matching_bracket("", 0, |_| true), None);
+    }
+
+    #[test]
+    fn test_matching_bracket_skips_non_code() {
+        let text = "(\")\")";
+        assert_eq!(matching_bracket(text, 0, |_| true), Some(2));
+        assert_eq!(matching_bracket(text, 0, |i| i != 2), Some(4));
+    }
+}
@@ -0,0 +1,106 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{Document, PieceTable};
+
+/// Builds a document from content streamed in chunks
+///
+/// Each chunk is appended to the original buffer and only its own line starts
+/// are computed, so a host can feed a large file piece by piece and read the
+/// lines ingested so far before the whole file has arrived. The result is the
+/// same as constructing a `Document` from the concatenated chunks.
+#[wasm_bindgen]
+pub struct DocumentBuilder {
+    piece_table: PieceTable,
+}
+
+#[wasm_bindgen]
+impl DocumentBuilder {
+    /// Create an empty builder
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> DocumentBuilder {
+        DocumentBuilder {
+            piece_table: PieceTable::new(String::new()),
+        }
+    }
+
+    /// Append the next chunk of content
+    #[wasm_bindgen(js_name = appendChunk)]
+    pub fn append_chunk(&mut self, chunk: &str) {
+        self.piece_table.append_original(chunk);
+    }
+
+    /// Get the number of bytes ingested so far
+    #[wasm_bindgen(js_name = getLength)]
+    pub fn get_length(&self) -> usize {
+        self.piece_table.get_length()
+    }
+
+    /// Get the number of lines ingested so far
+    /// The last line may still be incomplete
+    #[wasm_bindgen(js_name = getLineCount)]
+    pub fn get_line_count(&self) -> usize {
+        self.piece_table.get_line_count()
+    }
+
+    /// Get a line ingested so far, for displaying the top of a file early
+    #[wasm_bindgen(js_name = getLine)]
+    pub fn get_line(&self, line: usize) -> Option<String> {
+        self.piece_table.get_line(line)
+    }
+
+    /// Finish the document; the streamed content is its initial, non-undoable state
+    #[wasm_bindgen]
+    pub fn build(self) -> Document {
+        let mut document = Document::new(None);
+        document.piece_table = self.piece_table;
+        document
+    }
+}
+
+impl Default for DocumentBuilder {
+    fn default() -> Self {
+        DocumentBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_build_same_document() {
+        let text = "first line\nsecond\n\nthird caf\u{e9}\nlast";
+        let mut builder = DocumentBuilder::new();
+        for chunk in ["first li", "ne\nsec", "ond\n", "\nthird caf\u{e9}", "\nla", "st"] {
+            builder.append_chunk(chunk);
+        }
+        assert_eq!(builder.get_line(0), Some("first line".to_string()));
+        assert_eq!(builder.get_line_count(), 5);
+
+        let mut streamed = builder.build();
+        let whole = Document::new(Some(text.to_string()));
+        assert_eq!(streamed.get_text(), whole.get_text());
+        assert_eq!(streamed.get_line_count(), whole.get_line_count());
+        for line in 0..whole.get_line_count() {
+            assert_eq!(streamed.get_line(line), whole.get_line(line));
+        }
+        assert!(streamed.validate());
+        assert!(!streamed.can_undo());
+
+        streamed.insert(0, ">");
+        assert_eq!(streamed.get_line(0), Some(">first line".to_string()));
+        assert!(streamed.undo());
+        assert_eq!(streamed.get_text(), text);
+    }
+
+    #[test]
+    fn test_partial_lines_while_streaming() {
+        let mut builder = DocumentBuilder::new();
+        builder.append_chunk("top\nmid");
+        assert_eq!(builder.get_line(1), Some("mid".to_string()));
+        builder.append_chunk("dle\n");
+        assert_eq!(builder.get_line(1), Some("middle".to_string()));
+        assert_eq!(builder.get_line_count(), 3);
+        assert_eq!(builder.get_length(), 11);
+    }
+}
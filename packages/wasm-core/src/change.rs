@@ -0,0 +1,42 @@
+use serde::Serialize;
+
+use crate::operations::OperationType;
+
+/// Callback notified with each change event
+pub type ChangeListener = Box<dyn Fn(&ChangeEvent)>;
+
+/// A single content change reported to change listeners
+///
+/// Serialized as `{ opType, offset, removedLength, insertedText, lineDelta }`.
+/// Undoing or redoing a grouped edit reports one event per grouped operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEvent {
+    /// Insert, Delete or Replace, depending on what the change removed and added
+    pub op_type: OperationType,
+    /// Byte offset where the change starts
+    pub offset: usize,
+    /// Number of bytes removed at the offset
+    pub removed_length: usize,
+    /// Text inserted at the offset
+    pub inserted_text: String,
+    /// Change in the document's line count
+    pub line_delta: isize,
+}
+
+impl ChangeEvent {
+    pub fn new(offset: usize, removed_length: usize, inserted_text: &str, line_delta: isize) -> Self {
+        let op_type = match (removed_length, inserted_text.is_empty()) {
+            (0, _) => OperationType::Insert,
+            (_, true) => OperationType::Delete,
+            _ => OperationType::Replace,
+        };
+        ChangeEvent {
+            op_type,
+            offset,
+            removed_length,
+            inserted_text: inserted_text.to_string(),
+            line_delta,
+        }
+    }
+}
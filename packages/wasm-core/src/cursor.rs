@@ -0,0 +1,108 @@
+use wasm_bindgen::prelude::*;
+
+use crate::Document;
+
+/// Lazily reads a document one character at a time
+///
+/// The cursor walks the piece list directly instead of materializing the text.
+/// Because wasm-bindgen objects can't hold borrows, the document is passed to
+/// each call. Any edit to the document invalidates the cursor: afterwards
+/// `next` and `peek` return undefined and `isValid` returns false.
+#[wasm_bindgen]
+pub struct CharCursor {
+    version: u64,
+    piece_index: usize,
+    piece_offset: usize,
+    offset: usize,
+}
+
+impl CharCursor {
+    pub(crate) fn new(document: &Document, offset: usize) -> CharCursor {
+        let piece_table = &document.piece_table;
+        let offset = piece_table.floor_char_boundary(offset);
+        let (piece_index, piece_offset) = piece_table.locate(offset);
+        CharCursor {
+            version: piece_table.version(),
+            piece_index,
+            piece_offset,
+            offset,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl CharCursor {
+    /// Read the next character and advance past it
+    #[wasm_bindgen]
+    pub fn next(&mut self, document: &Document) -> Option<String> {
+        if !self.is_valid(document) {
+            return None;
+        }
+        let (c, piece_index, piece_offset) = document.piece_table.char_at(self.piece_index, self.piece_offset)?;
+        self.piece_index = piece_index;
+        self.piece_offset = piece_offset;
+        self.offset += c.len_utf8();
+        Some(c.to_string())
+    }
+
+    /// Read the next character without advancing
+    #[wasm_bindgen]
+    pub fn peek(&self, document: &Document) -> Option<String> {
+        if !self.is_valid(document) {
+            return None;
+        }
+        let (c, _, _) = document.piece_table.char_at(self.piece_index, self.piece_offset)?;
+        Some(c.to_string())
+    }
+
+    /// Check that the document hasn't been edited since the cursor was created
+    #[wasm_bindgen(js_name = isValid)]
+    pub fn is_valid(&self, document: &Document) -> bool {
+        self.version == document.piece_table.version()
+    }
+
+    /// Get the offset of the next character to be read
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Document;
+
+    #[test]
+    fn test_read_first_chars() {
+        let mut doc = Document::new(Some("h\u{e9}llo".to_string()));
+        doc.insert(6, " w\u{1f600}rld");
+        let mut cursor = doc.char_cursor(0);
+
+        let read: Vec<String> = (0..8).filter_map(|_| cursor.next(&doc)).collect();
+        assert_eq!(read.concat(), "h\u{e9}llo w\u{1f600}");
+        assert_eq!(cursor.offset(), 12);
+        assert_eq!(cursor.peek(&doc), Some("r".to_string()));
+        assert_eq!(cursor.next(&doc), Some("r".to_string()));
+    }
+
+    #[test]
+    fn test_cursor_end_and_mid_char_start() {
+        let doc = Document::new(Some("a\u{e9}".to_string()));
+        let mut cursor = doc.char_cursor(2);
+        assert_eq!(cursor.offset(), 1);
+        assert_eq!(cursor.next(&doc), Some("\u{e9}".to_string()));
+        assert_eq!(cursor.next(&doc), None);
+    }
+
+    #[test]
+    fn test_edit_invalidates_cursor() {
+        let mut doc = Document::new(Some("abc".to_string()));
+        let mut cursor = doc.char_cursor(0);
+        assert!(cursor.is_valid(&doc));
+
+        doc.insert(0, "x");
+        assert!(!cursor.is_valid(&doc));
+        assert_eq!(cursor.next(&doc), None);
+        assert_eq!(cursor.peek(&doc), None);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::operations::Operation;
+
+/// One step of an edit script over two sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Compute a shortest edit script turning `a` into `b` with Myers' algorithm
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let index = |k: isize| (k + max) as usize;
+
+    // v[k] holds the furthest x reached on diagonal k; trace keeps v before each round
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walk the trace backwards from the end to recover the path
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == prev_x { Edit::Insert } else { Edit::Delete });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    edits.reverse();
+    edits
+}
+
+/// Compute line-level insert and delete operations turning `old` into `new`
+/// The operations are meant to be applied in order: each offset accounts for
+/// the operations before it. Adjacent changed lines are merged into a single
+/// delete followed by a single insert, and unchanged lines are never touched
+pub fn line_diff(old: &str, new: &str) -> Vec<Operation> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let mut operations = Vec::new();
+    let mut offset = 0;
+    let mut deleted = String::new();
+    let mut inserted = String::new();
+    let (mut old_index, mut new_index) = (0, 0);
+
+    let mut flush = |offset: &mut usize, deleted: &mut String, inserted: &mut String| {
+        if !deleted.is_empty() {
+            operations.push(Operation::delete(*offset, std::mem::take(deleted)));
+        }
+        if !inserted.is_empty() {
+            let text = std::mem::take(inserted);
+            let length = text.len();
+            operations.push(Operation::insert(*offset, text));
+            *offset += length;
+        }
+    };
+
+    for edit in myers(&old_lines, &new_lines) {
+        match edit {
+            Edit::Equal => {
+                flush(&mut offset, &mut deleted, &mut inserted);
+                offset += old_lines[old_index].len();
+                old_index += 1;
+                new_index += 1;
+            }
+            Edit::Delete => {
+                deleted.push_str(old_lines[old_index]);
+                old_index += 1;
+            }
+            Edit::Insert => {
+                inserted.push_str(new_lines[new_index]);
+                new_index += 1;
+            }
+        }
+    }
+    flush(&mut offset, &mut deleted, &mut inserted);
+
+    operations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece_table::PieceTable;
+
+    fn apply(old: &str, operations: &[Operation]) -> String {
+        let mut pt = PieceTable::new(old.to_string());
+        for operation in operations {
+            match operation.op_type {
+                crate::operations::OperationType::Insert => pt.insert(operation.offset, &operation.text),
+                _ => pt.delete(operation.offset, operation.length),
+            }
+        }
+        pt.get_text()
+    }
+
+    #[test]
+    fn test_myers_minimal_script() {
+        let edits = myers(&['a', 'b', 'c', 'a', 'b', 'b', 'a'], &['c', 'b', 'a', 'b', 'a', 'c']);
+        assert_eq!(edits.iter().filter(|&&e| e != Edit::Equal).count(), 5);
+        assert_eq!(myers::<char>(&[], &[]), Vec::new());
+    }
+
+    #[test]
+    fn test_line_diff_round_trips() {
+        let cases = [
+            ("", "new\ntext"),
+            ("old\ntext", ""),
+            ("a\nb\nc\n", "a\nc\nd\n"),
+            ("same", "same"),
+            ("no newline", "no newline\n"),
+        ];
+        for (old, new) in cases {
+            assert_eq!(apply(old, &line_diff(old, new)), new, "{:?} -> {:?}", old, new);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+/// One edit of a batch, with its range expressed against the original document
+/// Deserialized from `{ offset, length, text }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub offset: usize,
+    #[serde(default)]
+    pub length: usize,
+    #[serde(default)]
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(offset: usize, length: usize, text: &str) -> Self {
+        TextEdit {
+            offset,
+            length,
+            text: text.to_string(),
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.offset.saturating_add(self.length)
+    }
+}
+
+/// Why a batch of edits was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EditErrorKind {
+    /// The edits could not be read as `{ offset, length, text }` objects
+    InvalidInput,
+    /// An edit's range runs past the document or splits a character
+    InvalidRange,
+    /// Two edits touch the same text, so their combined result is ambiguous
+    Overlap,
+}
+
+/// Error returned to JS as `{ kind, message, index }`
+/// `index` is the position in the batch of the offending edit, when there is one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EditError {
+    pub kind: EditErrorKind,
+    pub message: String,
+    pub index: Option<usize>,
+}
+
+impl EditError {
+    pub fn new(kind: EditErrorKind, message: String, index: Option<usize>) -> Self {
+        EditError { kind, message, index }
+    }
+}
+
+/// Validate a batch and return the edit indices in a safe application order
+/// Edits are applied back to front so earlier offsets stay valid. Inserts at the
+/// same offset keep their batch order in the result, and an insert may sit at
+/// the start of a replaced range only if it comes first in the batch
+pub fn application_order(
+    edits: &[TextEdit],
+    is_valid_range: impl Fn(usize, usize) -> bool,
+) -> Result<Vec<usize>, EditError> {
+    if let Some(index) = edits.iter().position(|edit| !is_valid_range(edit.offset, edit.length)) {
+        let edit = &edits[index];
+        let message = format!(
+            "edit {} at {}..{} is outside the document or splits a character",
+            index,
+            edit.offset,
+            edit.end()
+        );
+        return Err(EditError::new(EditErrorKind::InvalidRange, message, Some(index)));
+    }
+
+    let mut order: Vec<usize> = (0..edits.len()).collect();
+    order.sort_by_key(|&index| (edits[index].offset, index));
+    for pair in order.windows(2) {
+        let (previous, next) = (&edits[pair[0]], &edits[pair[1]]);
+        if previous.end() > next.offset {
+            let message = format!("edit {} overlaps edit {}", pair[1], pair[0]);
+            return Err(EditError::new(EditErrorKind::Overlap, message, Some(pair[1])));
+        }
+    }
+
+    order.reverse();
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(edits: &[TextEdit]) -> Result<Vec<usize>, EditError> {
+        application_order(edits, |offset, length| offset + length <= 10)
+    }
+
+    #[test]
+    fn test_order_is_back_to_front() {
+        let edits = [TextEdit::new(4, 2, "x"), TextEdit::new(0, 1, ""), TextEdit::new(8, 0, "y")];
+        assert_eq!(order(&edits), Ok(vec![2, 0, 1]));
+    }
+
+    #[test]
+    fn test_adjacent_and_same_offset_inserts_allowed() {
+        let edits = [TextEdit::new(2, 0, "a"), TextEdit::new(2, 0, "b"), TextEdit::new(2, 3, "c"), TextEdit::new(5, 1, "")];
+        assert_eq!(order(&edits), Ok(vec![3, 2, 1, 0]));
+    }
+
+    #[test]
+    fn test_overlap_rejected() {
+        let error = order(&[TextEdit::new(0, 4, "a"), TextEdit::new(3, 2, "b")]).unwrap_err();
+        assert_eq!(error.kind, EditErrorKind::Overlap);
+        assert_eq!(error.index, Some(1));
+
+        let error = order(&[TextEdit::new(2, 3, "c"), TextEdit::new(2, 0, "a")]).unwrap_err();
+        assert_eq!(error.kind, EditErrorKind::Overlap);
+    }
+
+    #[test]
+    fn test_invalid_range_rejected() {
+        let error = order(&[TextEdit::new(0, 1, ""), TextEdit::new(9, 2, "")]).unwrap_err();
+        assert_eq!(error.kind, EditErrorKind::InvalidRange);
+        assert_eq!(error.index, Some(1));
+    }
+}
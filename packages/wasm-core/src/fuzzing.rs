@@ -0,0 +1,78 @@
+use wasm_bindgen::prelude::*;
+
+use crate::Document;
+
+/// Fragments inserted by random edits, mixing ASCII, newlines and multibyte text
+const FRAGMENTS: [&str; 8] = ["a", "hello", " ", "\n", "\t", "caf\u{e9}", "\u{1f600}", "line\nbreak"];
+
+/// Small deterministic PRNG (SplitMix64) so edit sequences reproduce exactly
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u32) -> Self {
+        SplitMix64 { state: seed as u64 }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound.max(1) as u64) as usize
+    }
+}
+
+#[wasm_bindgen]
+impl Document {
+    /// Apply `count` pseudo-random valid edits derived from `seed`
+    /// The same seed always produces the same sequence of edits; returns the seed
+    #[wasm_bindgen(js_name = applyRandomEdits)]
+    pub fn apply_random_edits(&mut self, seed: u32, count: usize) -> u32 {
+        let mut rng = SplitMix64::new(seed);
+
+        for _ in 0..count {
+            let text = self.get_text();
+            let mut boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+            boundaries.push(text.len());
+
+            let start = boundaries[rng.below(boundaries.len())];
+            let end = boundaries[rng.below(boundaries.len())].max(start);
+            let fragment = FRAGMENTS[rng.below(FRAGMENTS.len())];
+
+            match rng.below(3) {
+                0 => self.insert(start, fragment),
+                1 => self.delete(start, end - start),
+                _ => self.replace(start, end - start, fragment),
+            };
+        }
+
+        seed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Document;
+
+    #[test]
+    fn test_same_seed_same_text() {
+        let mut a = Document::new(Some("seed text\nsecond line".to_string()));
+        let mut b = Document::new(Some("seed text\nsecond line".to_string()));
+
+        assert_eq!(a.apply_random_edits(42, 200), 42);
+        b.apply_random_edits(42, 200);
+        assert_eq!(a.get_text(), b.get_text());
+        assert_eq!(a.get_line_count(), a.get_text().matches('\n').count() + 1);
+        assert!(a.validate());
+
+        let mut c = Document::new(Some("seed text\nsecond line".to_string()));
+        c.apply_random_edits(7, 200);
+        assert_ne!(a.get_text(), c.get_text());
+    }
+}
@@ -0,0 +1,76 @@
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+/// Get the offset of the next extended grapheme cluster boundary after `offset`
+/// `offset` must be a char boundary; the end of the text is returned at or past it
+pub fn next_boundary(text: &str, offset: usize) -> usize {
+    if offset >= text.len() {
+        return text.len();
+    }
+    GraphemeCursor::new(offset, text.len(), true)
+        .next_boundary(text, 0)
+        .ok()
+        .flatten()
+        .unwrap_or(text.len())
+}
+
+/// Get the offset of the previous extended grapheme cluster boundary before `offset`
+/// `offset` must be a char boundary; 0 is returned at the start of the text
+pub fn prev_boundary(text: &str, offset: usize) -> usize {
+    if offset == 0 {
+        return 0;
+    }
+    GraphemeCursor::new(offset.min(text.len()), text.len(), true)
+        .prev_boundary(text, 0)
+        .ok()
+        .flatten()
+        .unwrap_or(0)
+}
+
+/// Check whether `offset` lies between two grapheme clusters
+pub fn is_boundary(text: &str, offset: usize) -> bool {
+    GraphemeCursor::new(offset, text.len(), true)
+        .is_boundary(text, 0)
+        .unwrap_or(false)
+}
+
+/// Count the grapheme clusters between a line start and `offset`
+/// An offset inside a cluster counts the clusters before it
+pub fn column(text: &str, line_start: usize, offset: usize) -> usize {
+    let end = if is_boundary(text, offset) { offset } else { prev_boundary(text, offset) };
+    text[line_start..end.max(line_start)].graphemes(true).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAMILY: &str = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+
+    #[test]
+    fn test_combining_accent_is_one_cluster() {
+        let text = "ae\u{301}b";
+        assert_eq!(next_boundary(text, 1), 4);
+        assert_eq!(prev_boundary(text, 4), 1);
+        assert_eq!(next_boundary(text, 2), 4);
+        assert_eq!(prev_boundary(text, 2), 1);
+        assert_eq!(next_boundary(text, 5), 5);
+        assert_eq!(prev_boundary(text, 0), 0);
+    }
+
+    #[test]
+    fn test_zwj_sequence_is_one_cluster() {
+        let text = format!("x{}y", FAMILY);
+        assert_eq!(FAMILY.len(), 18);
+        assert_eq!(next_boundary(&text, 1), 19);
+        assert_eq!(prev_boundary(&text, 19), 1);
+        assert_eq!(next_boundary(&text, 5), 19);
+        assert_eq!(column(&text, 0, 19), 2);
+        assert_eq!(column(&text, 0, 8), 1);
+    }
+
+    #[test]
+    fn test_crlf_is_one_cluster() {
+        assert_eq!(next_boundary("a\r\nb", 1), 3);
+        assert_eq!(prev_boundary("a\r\nb", 3), 1);
+    }
+}
@@ -0,0 +1,60 @@
+/// Streaming 64-bit FNV-1a hasher
+/// Stable across platforms and builds, unlike `std::hash::DefaultHasher`
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv1a64 {
+    state: u64,
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+impl Fnv1a64 {
+    pub fn new() -> Self {
+        Fnv1a64 { state: FNV_OFFSET_BASIS }
+    }
+
+    /// Feed bytes into the hash
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Get the hash of everything written so far
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+impl Default for Fnv1a64 {
+    fn default() -> Self {
+        Fnv1a64::new()
+    }
+}
+
+/// Hash a sequence of text chunks as if they were one string
+pub fn hash_chunks<'a>(chunks: impl IntoIterator<Item = &'a str>) -> u64 {
+    let mut hasher = Fnv1a64::new();
+    for chunk in chunks {
+        hasher.write(chunk.as_bytes());
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_values() {
+        assert_eq!(hash_chunks([""]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_chunks(["a"]), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_chunking_does_not_matter() {
+        assert_eq!(hash_chunks(["hello world"]), hash_chunks(["hel", "lo w", "orld"]));
+        assert_ne!(hash_chunks(["hello world"]), hash_chunks(["hello World"]));
+    }
+}
@@ -1,44 +1,275 @@
-use crate::operations::Operation;
+use std::collections::VecDeque;
 
-/// Maximum number of operations to keep in history
-const MAX_HISTORY_SIZE: usize = 1000;
+use serde::{Deserialize, Serialize};
+
+use crate::operations::{Operation, OperationType};
+use crate::persistence;
+
+/// Default maximum number of operations to keep in history
+const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+/// Default window in milliseconds within which adjacent edits are coalesced
+const DEFAULT_COALESCE_WINDOW_MS: f64 = 1000.0;
+
+/// A named point in the history that the document can be reverted to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub name: String,
+    /// Number of edits applied at the checkpoint, counting entries evicted by the limit
+    pub depth: usize,
+    /// Set once the checkpoint can no longer be reached by undo or redo
+    pub stale: bool,
+}
 
 /// Manages undo/redo history for document operations
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct History {
-    /// Stack of operations that can be undone
-    undo_stack: Vec<Operation>,
+    /// Stack of operations that can be undone, oldest at the front
+    undo_stack: VecDeque<Operation>,
     /// Stack of operations that can be redone
     redo_stack: Vec<Operation>,
+    /// Whether a push equal to the next redo entry consumes it instead of clearing redo
+    preserve_matching_redo: bool,
+    /// Maximum number of undo entries kept, or 0 for no limit
+    #[serde(default = "default_history_limit")]
+    limit: usize,
+    /// Nesting depth of open transactions
+    transaction_depth: usize,
+    /// Operations pushed since the outermost transaction was opened
+    transaction: Vec<Operation>,
+    /// Maximum gap in milliseconds between two coalesced edits
+    coalesce_window_ms: f64,
+    /// Timestamp of the last timed push, or None when the next edit must start a new entry
+    #[serde(skip)]
+    last_push_time: Option<f64>,
+    /// Number of undo entries dropped from the front by the limit
+    #[serde(default)]
+    evicted: usize,
+    /// Named checkpoints in creation order
+    #[serde(default)]
+    checkpoints: Vec<Checkpoint>,
 }
 
 impl History {
     pub fn new() -> Self {
         History {
-            undo_stack: Vec::new(),
+            undo_stack: VecDeque::new(),
             redo_stack: Vec::new(),
+            preserve_matching_redo: false,
+            limit: DEFAULT_HISTORY_LIMIT,
+            transaction_depth: 0,
+            transaction: Vec::new(),
+            coalesce_window_ms: DEFAULT_COALESCE_WINDOW_MS,
+            last_push_time: None,
+            evicted: 0,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Enable or disable keeping the redo stack when an edit repeats the next redo entry
+    pub fn set_preserve_matching_redo(&mut self, enabled: bool) {
+        self.preserve_matching_redo = enabled;
+    }
+
+    /// Set the maximum number of undo entries to keep, with 0 meaning unlimited
+    /// The oldest entries beyond a lowered limit are dropped immediately
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim_to_limit();
+    }
+
+    /// Set the maximum gap in milliseconds between edits that are coalesced
+    pub fn set_coalesce_window(&mut self, window_ms: f64) {
+        self.coalesce_window_ms = window_ms;
+    }
+
+    /// Make the next timed push start a new undo entry
+    pub fn break_coalescing(&mut self) {
+        self.last_push_time = None;
+    }
+
+    /// Push an operation made at `timestamp_ms`, merging it into the top entry
+    /// when it continues that edit within the coalesce window
+    /// Inserts merge when they extend the previous insert; deletes merge when
+    /// they remove text directly before (backspace) or at (forward delete) the
+    /// previous delete. Typing whitespace after a word starts a new entry
+    pub fn push_with_time(&mut self, operation: Operation, timestamp_ms: f64) {
+        let within_window = self
+            .last_push_time
+            .is_some_and(|last| timestamp_ms - last >= 0.0 && timestamp_ms - last < self.coalesce_window_ms);
+        let top = if self.in_transaction() {
+            self.transaction.last_mut()
+        } else {
+            self.undo_stack.back_mut()
+        };
+
+        let merged = within_window && top.is_some_and(|top| Self::coalesce(top, &operation));
+        if merged {
+            if !self.in_transaction() {
+                self.redo_stack.clear();
+            }
+        } else {
+            self.push(operation);
+        }
+        self.last_push_time = Some(timestamp_ms);
+    }
+
+    /// Fold `next` into `top` if it continues the same edit
+    fn coalesce(top: &mut Operation, next: &Operation) -> bool {
+        match (top.op_type, next.op_type) {
+            (OperationType::Insert, OperationType::Insert) => {
+                let starts_word = next.text.starts_with(char::is_whitespace)
+                    && !top.text.ends_with(char::is_whitespace);
+                if top.offset + top.length != next.offset || starts_word {
+                    return false;
+                }
+                top.text.push_str(&next.text);
+            }
+            (OperationType::Delete, OperationType::Delete) => {
+                if next.offset + next.length == top.offset {
+                    top.offset = next.offset;
+                    top.text.insert_str(0, &next.text);
+                } else if next.offset == top.offset {
+                    top.text.push_str(&next.text);
+                } else {
+                    return false;
+                }
+            }
+            _ => return false,
         }
+        top.length += next.length;
+        true
+    }
+
+    /// Open a transaction; operations pushed until the matching `end_transaction`
+    /// are recorded as one undo entry. Transactions may be nested
+    pub fn begin_transaction(&mut self) {
+        self.transaction_depth += 1;
+    }
+
+    /// Close the innermost transaction
+    /// Closing the outermost one records the collected operations as a single
+    /// entry. Returns false if no transaction is open
+    pub fn end_transaction(&mut self) -> bool {
+        if self.transaction_depth == 0 {
+            return false;
+        }
+
+        self.transaction_depth -= 1;
+        if self.transaction_depth == 0 {
+            let mut operations = std::mem::take(&mut self.transaction);
+            match operations.len() {
+                0 => {}
+                1 => self.record(operations.pop().unwrap()),
+                _ => self.record(Operation::compound(operations)),
+            }
+        }
+        true
+    }
+
+    /// Check if a transaction is currently open
+    pub fn in_transaction(&self) -> bool {
+        self.transaction_depth > 0
     }
 
     /// Push a new operation onto the undo stack
-    /// This clears the redo stack as the history has diverged
+    /// Inside a transaction the operation is collected until the transaction ends
     pub fn push(&mut self, operation: Operation) {
-        // Clear redo stack when a new operation is performed
-        self.redo_stack.clear();
+        self.last_push_time = None;
+        if self.in_transaction() {
+            self.transaction.push(operation);
+        } else {
+            self.record(operation);
+        }
+    }
+
+    /// Record an operation as a new undo entry
+    /// This clears the redo stack as the history has diverged, unless matching
+    /// redo preservation is enabled and the operation equals the next redo entry
+    fn record(&mut self, operation: Operation) {
+        if self.preserve_matching_redo && self.redo_stack.last() == Some(&operation) {
+            // The edit re-applies what was undone, so treat it as a redo
+            self.redo_stack.pop();
+        } else {
+            // Clear redo stack when a new operation is performed
+            self.redo_stack.clear();
+            // Checkpoints on the discarded redo branch can't be reached any more
+            let depth = self.depth();
+            self.mark_stale(|checkpoint| checkpoint.depth > depth);
+        }
 
         // Add to undo stack
-        self.undo_stack.push(operation);
+        self.undo_stack.push_back(operation);
+        self.trim_to_limit();
+    }
+
+    /// Drop the oldest undo entries beyond the limit
+    fn trim_to_limit(&mut self) {
+        if self.limit == 0 {
+            return;
+        }
+        while self.undo_stack.len() > self.limit {
+            self.undo_stack.pop_front();
+            self.evicted += 1;
+        }
+        let evicted = self.evicted;
+        self.mark_stale(|checkpoint| checkpoint.depth < evicted);
+    }
+
+    /// Number of edits applied to reach the current state, including evicted ones
+    pub fn depth(&self) -> usize {
+        self.evicted + self.undo_stack.len()
+    }
 
-        // Limit history size
-        if self.undo_stack.len() > MAX_HISTORY_SIZE {
-            self.undo_stack.remove(0);
+    /// Tag the current state with a name, replacing any checkpoint of the same name
+    /// The next edit starts a new undo entry so it is never coalesced across the
+    /// checkpoint. Returns false while a transaction is open
+    pub fn checkpoint(&mut self, name: &str) -> bool {
+        if self.in_transaction() {
+            return false;
+        }
+        self.break_coalescing();
+        self.checkpoints.retain(|checkpoint| checkpoint.name != name);
+        self.checkpoints.push(Checkpoint {
+            name: name.to_string(),
+            depth: self.depth(),
+            stale: false,
+        });
+        true
+    }
+
+    /// Look up a checkpoint by name
+    pub fn get_checkpoint(&self, name: &str) -> Option<&Checkpoint> {
+        self.checkpoints.iter().find(|checkpoint| checkpoint.name == name)
+    }
+
+    /// Get all checkpoints in creation order
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Remove a checkpoint, returning false if there was none with that name
+    pub fn remove_checkpoint(&mut self, name: &str) -> bool {
+        let count = self.checkpoints.len();
+        self.checkpoints.retain(|checkpoint| checkpoint.name != name);
+        self.checkpoints.len() != count
+    }
+
+    fn mark_stale(&mut self, predicate: impl Fn(&Checkpoint) -> bool) {
+        for checkpoint in self.checkpoints.iter_mut().filter(|checkpoint| predicate(checkpoint)) {
+            checkpoint.stale = true;
         }
     }
 
     /// Undo the last operation
-    /// Returns the operation that was undone, if any
+    /// Returns the operation that was undone, if any; nothing is undone while a
+    /// transaction is open
     pub fn undo(&mut self) -> Option<Operation> {
-        if let Some(operation) = self.undo_stack.pop() {
+        if self.in_transaction() {
+            return None;
+        }
+        self.last_push_time = None;
+        if let Some(operation) = self.undo_stack.pop_back() {
             self.redo_stack.push(operation.clone());
             Some(operation)
         } else {
@@ -47,16 +278,55 @@ impl History {
     }
 
     /// Redo the last undone operation
-    /// Returns the operation that was redone, if any
+    /// Returns the operation that was redone, if any; nothing is redone while a
+    /// transaction is open
     pub fn redo(&mut self) -> Option<Operation> {
+        if self.in_transaction() {
+            return None;
+        }
+        self.last_push_time = None;
         if let Some(operation) = self.redo_stack.pop() {
-            self.undo_stack.push(operation.clone());
+            self.undo_stack.push_back(operation.clone());
+            self.trim_to_limit();
             Some(operation)
         } else {
             None
         }
     }
 
+    /// Combine the two most recent undo entries into a single compound entry
+    /// Returns false if there are fewer than two entries; the redo stack is untouched
+    pub fn merge_last_two(&mut self) -> bool {
+        if self.undo_stack.len() < 2 {
+            return false;
+        }
+        let between = self.depth() - 1;
+        let last = self.undo_stack.pop_back().unwrap();
+        let previous = self.undo_stack.pop_back().unwrap();
+        self.undo_stack.push_back(Operation::compound(vec![previous, last]));
+
+        // A checkpoint between the merged edits no longer has an undo entry
+        // boundary, and everything above it moves down one entry
+        for checkpoint in &mut self.checkpoints {
+            if checkpoint.depth == between {
+                checkpoint.stale = true;
+            } else if checkpoint.depth > between {
+                checkpoint.depth -= 1;
+            }
+        }
+        true
+    }
+
+    /// Get the operation the next `undo` would revert, without reverting it
+    pub fn peek_undo(&self) -> Option<&Operation> {
+        self.undo_stack.back()
+    }
+
+    /// Get the operation the next `redo` would reapply, without reapplying it
+    pub fn peek_redo(&self) -> Option<&Operation> {
+        self.redo_stack.last()
+    }
+
     /// Check if undo is available
     pub fn can_undo(&self) -> bool {
         !self.undo_stack.is_empty()
@@ -67,6 +337,46 @@ impl History {
         !self.redo_stack.is_empty()
     }
 
+    /// Approximate heap bytes held by the undo and redo stacks, an open
+    /// transaction and checkpoint names
+    pub fn memory_bytes(&self) -> usize {
+        let slots = self.undo_stack.capacity() + self.redo_stack.capacity() + self.transaction.capacity();
+        let operations: usize = self
+            .undo_stack
+            .iter()
+            .chain(&self.redo_stack)
+            .chain(&self.transaction)
+            .map(Operation::heap_bytes)
+            .sum();
+        let checkpoints: usize = self
+            .checkpoints
+            .iter()
+            .map(|checkpoint| size_of::<Checkpoint>() + checkpoint.name.capacity())
+            .sum();
+        slots * size_of::<Operation>() + operations + checkpoints
+    }
+
+    /// Get the undoable operations, oldest first
+    pub fn undo_operations(&self) -> &VecDeque<Operation> {
+        &self.undo_stack
+    }
+
+    /// Get the redoable operations, next redo last
+    pub fn redo_operations(&self) -> &[Operation] {
+        &self.redo_stack
+    }
+
+    /// Serialize the undo and redo stacks, limit and checkpoints to versioned JSON
+    pub fn export(&self) -> String {
+        persistence::history_to_json(self)
+    }
+
+    /// Parse a history produced by `export`
+    /// Returns an error message for malformed JSON or an unsupported version
+    pub fn import(json: &str) -> Result<History, String> {
+        persistence::history_from_json(json)
+    }
+
     /// Get the number of operations in the undo stack
     pub fn undo_count(&self) -> usize {
         self.undo_stack.len()
@@ -77,13 +387,23 @@ impl History {
         self.redo_stack.len()
     }
 
-    /// Clear all history
+    /// Clear all history, including operations collected by an open transaction
+    /// Only checkpoints at the current state stay reachable
     pub fn clear(&mut self) {
+        let depth = self.depth();
+        self.mark_stale(|checkpoint| checkpoint.depth != depth);
+        self.evicted = depth;
         self.undo_stack.clear();
         self.redo_stack.clear();
+        self.transaction.clear();
+        self.last_push_time = None;
     }
 }
 
+fn default_history_limit() -> usize {
+    DEFAULT_HISTORY_LIMIT
+}
+
 impl Default for History {
     fn default() -> Self {
         History::new()
@@ -93,7 +413,6 @@ impl Default for History {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::operations::OperationType;
 
     #[test]
     fn test_push_and_undo() {
@@ -139,4 +458,232 @@ mod tests {
         history.push(op2);
         assert!(!history.can_redo());
     }
+
+    #[test]
+    fn test_matching_push_preserves_redo() {
+        let mut history = History::new();
+        history.set_preserve_matching_redo(true);
+
+        let op1 = Operation::new(OperationType::Insert, 0, 5, "hello".to_string());
+        let op2 = Operation::new(OperationType::Insert, 5, 6, " world".to_string());
+        history.push(op1.clone());
+        history.push(op2.clone());
+        history.undo();
+        history.undo();
+
+        history.push(op1);
+        assert_eq!(history.undo_count(), 1);
+        assert_eq!(history.redo(), Some(op2));
+
+        history.undo();
+        history.push(Operation::new(OperationType::Insert, 5, 1, "!".to_string()));
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_merge_last_two() {
+        let mut history = History::new();
+        assert!(!history.merge_last_two());
+
+        let keystroke = Operation::insert(0, "(".to_string());
+        let auto_close = Operation::insert(1, ")".to_string());
+        history.push(keystroke.clone());
+        assert!(!history.merge_last_two());
+        history.push(auto_close.clone());
+
+        assert!(history.merge_last_two());
+        assert_eq!(history.undo_count(), 1);
+        let merged = history.undo().unwrap();
+        assert_eq!(merged.op_type, OperationType::Compound);
+        assert_eq!(merged.operations, vec![keystroke, auto_close]);
+    }
+
+    #[test]
+    fn test_matching_push_clears_redo_by_default() {
+        let mut history = History::new();
+        let op = Operation::new(OperationType::Insert, 0, 5, "hello".to_string());
+        history.push(op.clone());
+        history.undo();
+
+        history.push(op);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_transaction_records_one_entry() {
+        let mut history = History::new();
+        history.begin_transaction();
+        history.push(Operation::new(OperationType::Insert, 0, 1, "a".to_string()));
+        history.push(Operation::new(OperationType::Insert, 1, 1, "b".to_string()));
+        assert!(!history.can_undo());
+        assert!(history.undo().is_none());
+
+        assert!(history.end_transaction());
+        assert_eq!(history.undo_count(), 1);
+        let entry = history.undo().unwrap();
+        assert_eq!(entry.op_type, OperationType::Compound);
+        assert_eq!(entry.operations.len(), 2);
+    }
+
+    #[test]
+    fn test_nested_and_unbalanced_transactions() {
+        let mut history = History::new();
+        assert!(!history.end_transaction());
+
+        history.begin_transaction();
+        history.push(Operation::new(OperationType::Insert, 0, 1, "a".to_string()));
+        history.begin_transaction();
+        history.push(Operation::new(OperationType::Insert, 1, 1, "b".to_string()));
+        assert!(history.end_transaction());
+        assert!(history.in_transaction());
+        assert_eq!(history.undo_count(), 0);
+
+        assert!(history.end_transaction());
+        assert!(!history.end_transaction());
+        assert_eq!(history.undo_count(), 1);
+
+        history.begin_transaction();
+        assert!(history.end_transaction());
+        assert_eq!(history.undo_count(), 1);
+    }
+
+    fn type_text(history: &mut History, start: usize, text: &str, start_time: f64) {
+        for (i, c) in text.char_indices() {
+            history.push_with_time(Operation::insert(start + i, c.to_string()), start_time + i as f64 * 50.0);
+        }
+    }
+
+    #[test]
+    fn test_quick_typing_coalesces() {
+        let mut history = History::new();
+        type_text(&mut history, 0, "hello", 0.0);
+        assert_eq!(history.undo_count(), 1);
+        assert_eq!(history.undo().unwrap().text, "hello");
+    }
+
+    #[test]
+    fn test_break_coalescing_splits_words() {
+        let mut history = History::new();
+        type_text(&mut history, 0, "hello", 0.0);
+        history.break_coalescing();
+        history.push_with_time(Operation::insert(5, " ".to_string()), 300.0);
+        type_text(&mut history, 6, "world", 350.0);
+        assert_eq!(history.undo_count(), 2);
+        assert_eq!(history.undo().unwrap().text, " world");
+        assert_eq!(history.undo().unwrap().text, "hello");
+    }
+
+    #[test]
+    fn test_coalescing_respects_window_and_whitespace() {
+        let mut history = History::new();
+        history.set_coalesce_window(100.0);
+        history.push_with_time(Operation::insert(0, "a".to_string()), 0.0);
+        history.push_with_time(Operation::insert(1, "b".to_string()), 500.0);
+        assert_eq!(history.undo_count(), 2);
+
+        history.push_with_time(Operation::insert(2, " ".to_string()), 550.0);
+        history.push_with_time(Operation::insert(3, " ".to_string()), 600.0);
+        assert_eq!(history.undo_count(), 3);
+
+        history.push_with_time(Operation::insert(9, "c".to_string()), 650.0);
+        assert_eq!(history.undo_count(), 4);
+    }
+
+    #[test]
+    fn test_backspace_coalesces() {
+        let mut history = History::new();
+        history.push_with_time(Operation::delete(4, "d".to_string()), 0.0);
+        history.push_with_time(Operation::delete(3, "c".to_string()), 50.0);
+        history.push_with_time(Operation::delete(3, "e".to_string()), 100.0);
+        assert_eq!(history.undo_count(), 1);
+
+        let entry = history.undo().unwrap();
+        assert_eq!(entry.offset, 3);
+        assert_eq!(entry.text, "cde");
+        assert_eq!(entry.length, 3);
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let mut history = History::new();
+        history.set_limit(3);
+        for i in 0..5 {
+            history.push(Operation::insert(i, "x".to_string()));
+        }
+        assert_eq!(history.undo_count(), 3);
+        assert_eq!(history.undo_operations()[0].offset, 2);
+
+        history.set_limit(1);
+        assert_eq!(history.undo_count(), 1);
+        assert_eq!(history.undo().unwrap().offset, 4);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_unlimited_history() {
+        let mut history = History::new();
+        history.set_limit(0);
+        for i in 0..DEFAULT_HISTORY_LIMIT + 10 {
+            history.push(Operation::insert(i, "x".to_string()));
+        }
+        assert_eq!(history.undo_count(), DEFAULT_HISTORY_LIMIT + 10);
+    }
+
+    #[test]
+    fn test_checkpoint_tracks_depth() {
+        let mut history = History::new();
+        history.push(Operation::insert(0, "a".to_string()));
+        assert!(history.checkpoint("saved"));
+        history.push(Operation::insert(1, "b".to_string()));
+        history.push(Operation::insert(2, "c".to_string()));
+        assert_eq!(history.get_checkpoint("saved").map(|c| c.depth), Some(1));
+
+        history.merge_last_two();
+        assert_eq!(history.depth(), 2);
+        assert!(!history.get_checkpoint("saved").unwrap().stale);
+    }
+
+    #[test]
+    fn test_checkpoint_stale_after_divergence() {
+        let mut history = History::new();
+        history.push(Operation::insert(0, "a".to_string()));
+        history.push(Operation::insert(1, "b".to_string()));
+        history.checkpoint("tip");
+        history.undo();
+        history.undo();
+        history.checkpoint("start");
+
+        history.push(Operation::insert(0, "x".to_string()));
+        assert!(history.get_checkpoint("tip").unwrap().stale);
+        assert!(!history.get_checkpoint("start").unwrap().stale);
+    }
+
+    #[test]
+    fn test_checkpoint_stale_after_eviction_or_merge() {
+        let mut history = History::new();
+        history.set_limit(2);
+        history.checkpoint("empty");
+        history.push(Operation::insert(0, "a".to_string()));
+        history.checkpoint("one");
+        history.push(Operation::insert(1, "b".to_string()));
+        assert!(!history.get_checkpoint("empty").unwrap().stale);
+
+        history.push(Operation::insert(2, "c".to_string()));
+        assert!(history.get_checkpoint("empty").unwrap().stale);
+        assert!(!history.get_checkpoint("one").unwrap().stale);
+
+        history.checkpoint("two");
+        history.push(Operation::insert(3, "d".to_string()));
+        history.merge_last_two();
+        assert!(history.get_checkpoint("two").unwrap().stale);
+    }
+
+    #[test]
+    fn test_checkpoint_rejected_in_transaction() {
+        let mut history = History::new();
+        history.begin_transaction();
+        assert!(!history.checkpoint("inside"));
+        history.end_transaction();
+        assert!(history.checkpoints().is_empty());
+    }
 }
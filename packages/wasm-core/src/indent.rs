@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+/// Split a line into its leading whitespace and the rest
+fn split_indent(line: &str) -> (&str, &str) {
+    let rest = line.trim_start_matches([' ', '\t']);
+    (&line[..line.len() - rest.len()], rest)
+}
+
+/// Detect the indentation unit (in spaces) used by space-indented lines
+/// Uses the most common increase in indentation between consecutive non-blank
+/// lines; returns None if no line is indented with spaces
+pub fn detect_indent_unit<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<usize> {
+    let mut deltas: HashMap<usize, usize> = HashMap::new();
+    let mut previous = 0;
+
+    for line in lines {
+        let (indent, rest) = split_indent(line);
+        if rest.trim().is_empty() || indent.contains('\t') {
+            continue;
+        }
+        let width = indent.len();
+        if width > previous {
+            *deltas.entry(width - previous).or_insert(0) += 1;
+        }
+        previous = width;
+    }
+
+    deltas
+        .into_iter()
+        .max_by_key(|&(delta, count)| (count, std::cmp::Reverse(delta)))
+        .map(|(delta, _)| delta)
+}
+
+/// Re-express a line's indentation as whole levels of `spaces_per_level` spaces
+/// The current level is the indentation width divided by `unit`, rounded to the
+/// nearest level; a tab counts as one level. Blank lines are returned unchanged.
+pub fn reindent_line(line: &str, unit: usize, spaces_per_level: usize) -> String {
+    let (indent, rest) = split_indent(line);
+    if rest.trim().is_empty() {
+        return line.to_string();
+    }
+
+    let unit = unit.max(1);
+    let width: usize = indent.chars().map(|c| if c == '\t' { unit } else { 1 }).sum();
+    let level = (width + unit / 2) / unit;
+    format!("{}{}", " ".repeat(level * spaces_per_level), rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_indent_unit() {
+        let text = "a\n  b\n    c\n  d\n\ne";
+        assert_eq!(detect_indent_unit(text.split('\n')), Some(2));
+        assert_eq!(detect_indent_unit("a\nb".split('\n')), None);
+        assert_eq!(detect_indent_unit("a\n\tb".split('\n')), None);
+    }
+
+    #[test]
+    fn test_reindent_line_snaps_to_nearest_level() {
+        assert_eq!(reindent_line("    x", 2, 4), "        x");
+        assert_eq!(reindent_line("   x", 2, 4), "        x");
+        assert_eq!(reindent_line(" x", 4, 2), "x");
+        assert_eq!(reindent_line("\t  x", 2, 3), "      x");
+        assert_eq!(reindent_line("   ", 2, 4), "   ");
+    }
+}
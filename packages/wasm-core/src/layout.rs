@@ -0,0 +1,165 @@
+/// Visual width of a character starting at the given visual column
+/// Tabs advance to the next tab stop; every other character is one column wide
+pub fn char_width(c: char, visual_column: usize, tab_width: usize) -> usize {
+    if c == '\t' {
+        let tab_width = tab_width.max(1);
+        tab_width - visual_column % tab_width
+    } else {
+        1
+    }
+}
+
+/// Visual column of a byte column within a line, expanding tabs
+/// Columns past the line end are clamped to it
+pub fn visual_column(line: &str, column: usize, tab_width: usize) -> usize {
+    let mut visual = 0;
+    for (i, c) in line.char_indices() {
+        if i >= column {
+            break;
+        }
+        visual += char_width(c, visual, tab_width);
+    }
+    visual
+}
+
+/// Byte column of the character at a visual column, expanding tabs
+/// A visual column inside a tab maps to the tab's start; columns past the line
+/// end are clamped to it
+pub fn column_for_visual(line: &str, visual_column: usize, tab_width: usize) -> usize {
+    let mut visual = 0;
+    for (i, c) in line.char_indices() {
+        let width = char_width(c, visual, tab_width);
+        if visual + width > visual_column {
+            return i;
+        }
+        visual += width;
+    }
+    line.len()
+}
+
+/// Move a byte column by `delta` visual columns, snapping across tab stops
+/// Moving right lands on the first char boundary at or past the target column,
+/// moving left on the last one at or before it. The result is clamped to the line.
+pub fn move_by_visual_column(line: &str, column: usize, delta: isize, tab_width: usize) -> usize {
+    let mut boundaries = Vec::with_capacity(line.len() + 1);
+    let mut visual = 0;
+    for (i, c) in line.char_indices() {
+        boundaries.push((i, visual));
+        visual += char_width(c, visual, tab_width);
+    }
+    boundaries.push((line.len(), visual));
+
+    let index = boundaries.partition_point(|&(i, _)| i <= column).saturating_sub(1);
+    let (current, current_visual) = boundaries[index];
+    let target = current_visual.saturating_add_signed(delta);
+
+    if delta > 0 {
+        boundaries
+            .iter()
+            .find(|&&(_, v)| v >= target)
+            .map_or(line.len(), |&(i, _)| i)
+    } else if delta < 0 {
+        boundaries
+            .iter()
+            .rev()
+            .find(|&&(_, v)| v <= target)
+            .map_or(0, |&(i, _)| i)
+    } else {
+        current
+    }
+}
+
+/// Compute where a line wraps when rendered within `max_columns` visual columns
+/// Returns the byte offsets (relative to the line start) at which each
+/// continuation row begins. Lines break after whitespace when possible and
+/// fall back to a hard break inside words longer than the limit. Trailing
+/// whitespace is allowed to hang past the limit.
+pub fn wrap_points(line: &str, max_columns: usize, tab_width: usize) -> Vec<usize> {
+    let mut points = Vec::new();
+    if max_columns == 0 {
+        return points;
+    }
+
+    let mut column = 0;
+    let mut row_start = 0;
+    let mut row_start_column = 0;
+    let mut last_break = None;
+
+    for (i, c) in line.char_indices() {
+        let width = char_width(c, column, tab_width);
+
+        if !c.is_whitespace() && i > row_start && column + width - row_start_column > max_columns {
+            let (break_offset, break_column) = match last_break {
+                Some((offset, break_column)) if offset > row_start => (offset, break_column),
+                _ => (i, column),
+            };
+            points.push(break_offset);
+            row_start = break_offset;
+            row_start_column = break_column;
+        }
+
+        column += width;
+        if c.is_whitespace() {
+            last_break = Some((i + c.len_utf8(), column));
+        }
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_width_tabs() {
+        assert_eq!(char_width('a', 0, 4), 1);
+        assert_eq!(char_width('\t', 0, 4), 4);
+        assert_eq!(char_width('\t', 1, 4), 3);
+        assert_eq!(char_width('\t', 4, 4), 4);
+    }
+
+    #[test]
+    fn test_visual_column_expands_tabs() {
+        assert_eq!(visual_column("a\tb", 2, 4), 4);
+        assert_eq!(visual_column("a\tb", 1, 4), 1);
+        assert_eq!(visual_column("a\tb", 99, 4), 5);
+        assert_eq!(visual_column("ab\t\tc", 4, 4), 8);
+    }
+
+    #[test]
+    fn test_column_for_visual() {
+        assert_eq!(column_for_visual("a\tb", 4, 4), 2);
+        assert_eq!(column_for_visual("a\tb", 2, 4), 1);
+        assert_eq!(column_for_visual("a\tb", 1, 4), 1);
+        assert_eq!(column_for_visual("a\tb", 99, 4), 3);
+    }
+
+    #[test]
+    fn test_move_by_visual_column_across_tab() {
+        let line = "a\tb";
+        assert_eq!(move_by_visual_column(line, 1, 1, 4), 2);
+        assert_eq!(move_by_visual_column(line, 2, -1, 4), 1);
+        assert_eq!(move_by_visual_column(line, 0, 3, 4), 2);
+    }
+
+    #[test]
+    fn test_move_by_visual_column_clamps() {
+        let line = "a\tb";
+        assert_eq!(move_by_visual_column(line, 2, 10, 4), 3);
+        assert_eq!(move_by_visual_column(line, 1, -10, 4), 0);
+        assert_eq!(move_by_visual_column(line, 99, 0, 4), 3);
+    }
+
+    #[test]
+    fn test_wrap_points_at_word_boundaries() {
+        assert_eq!(wrap_points("aaaa bbbb cccc", 5, 4), vec![5, 10]);
+        assert_eq!(wrap_points("short", 20, 4), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_wrap_points_hard_break() {
+        assert_eq!(wrap_points("abcdefghij", 4, 4), vec![4, 8]);
+        assert_eq!(wrap_points("ab abcdefgh", 4, 4), vec![3, 7]);
+    }
+}
@@ -2,19 +2,80 @@ mod piece_table;
 mod operations;
 mod history;
 mod position;
+mod brackets;
+mod hash;
+mod layout;
+mod search;
+mod cursor;
+mod stats;
+mod patch;
+mod indent;
+mod analysis;
+mod marker;
+mod regex_search;
+mod persistence;
+mod word;
+mod change;
+mod diff;
+mod builder;
+mod search_cursor;
+mod edits;
+mod grapheme;
+#[cfg(any(test, feature = "fuzzing"))]
+mod fuzzing;
 
+use std::collections::HashMap;
+
+use serde::Deserialize;
 use wasm_bindgen::prelude::*;
 
-pub use piece_table::PieceTable;
-pub use operations::{Operation, OperationType};
-pub use history::History;
+pub use piece_table::{MemoryUsage, PieceSnapshot, PieceTable, PieceTableStats};
+pub use operations::{Operation, OperationSummary, OperationType};
+pub use history::{Checkpoint, History};
 pub use position::{Position, Range};
+pub use cursor::CharCursor;
+pub use builder::DocumentBuilder;
+pub use search_cursor::SearchCursor;
+pub use stats::{DocumentStats, SelectionStats};
+pub use patch::{OffsetUnit, PatchEntry, PatchOp};
+pub use analysis::{ContentReport, LineEnding, TextCharacteristics};
+pub use brackets::TokenKind;
+pub use marker::{Marker, MarkerBias};
+pub use search::SearchOptions;
+pub use change::{ChangeEvent, ChangeListener};
+pub use regex_search::{RegexError, RegexErrorKind, RegexMatchRange};
+pub use edits::{EditError, EditErrorKind, TextEdit};
 
 /// Document represents the main text document with editing capabilities
+///
+/// All offsets, lengths and columns are measured in UTF-8 bytes unless a method
+/// says otherwise. Offsets must fall on a char boundary: mutators reject an
+/// offset inside a multi-byte character by returning false, and range readers
+/// return None. Use `charOffsetOfLine` and the UTF-16 helpers to translate from
+/// other units.
+///
+/// Methods with a `Utf16` suffix (`insertUtf16`, `deleteUtf16`, `getLengthUtf16`,
+/// `offsetToPositionUtf16`, `positionToOffsetUtf16`, `utf16RangeByteLength`)
+/// take and return UTF-16 code units instead, matching JavaScript string
+/// indices. An emoji outside the BMP counts as two units, and a UTF-16 offset
+/// between the two halves of a surrogate pair is rejected.
 #[wasm_bindgen]
 pub struct Document {
     piece_table: PieceTable,
     history: History,
+    /// Captured states for time-travel, keyed by state id
+    states: HashMap<u32, PieceSnapshot>,
+    next_state_id: u32,
+    /// Host-provided classifier used to skip brackets in strings and comments
+    token_classifier: Option<Box<dyn Fn(usize) -> TokenKind>>,
+    /// Offsets that shift with edits, keyed by marker id
+    markers: HashMap<u32, Marker>,
+    next_marker_id: u32,
+    /// When set, every method that would change the content is a no-op
+    read_only: bool,
+    /// Callbacks notified after every content change, in registration order
+    change_listeners: Vec<(u32, ChangeListener)>,
+    next_listener_id: u32,
 }
 
 #[wasm_bindgen]
@@ -25,21 +86,67 @@ impl Document {
         Document {
             piece_table: PieceTable::new(initial_content.unwrap_or_default()),
             history: History::new(),
+            states: HashMap::new(),
+            next_state_id: 0,
+            token_classifier: None,
+            markers: HashMap::new(),
+            next_marker_id: 0,
+            read_only: false,
+            change_listeners: Vec::new(),
+            next_listener_id: 0,
         }
     }
 
+    /// Serialize the content and undo/redo history to a versioned JSON string
+    /// Markers, captured states and the token classifier are not included
+    #[wasm_bindgen]
+    pub fn serialize(&self) -> String {
+        persistence::to_json(&self.piece_table, &self.history)
+    }
+
+    /// Rebuild a document from the output of `serialize`
+    /// Throws an error message if the JSON is malformed, from an unsupported
+    /// format version, or describes an inconsistent piece table
+    #[wasm_bindgen]
+    pub fn deserialize(json: &str) -> Result<Document, JsValue> {
+        Self::from_json(json).map_err(|message| JsValue::from_str(&message))
+    }
+
+    /// Serialize only the undo/redo history, without the document text
+    #[wasm_bindgen(js_name = exportHistory)]
+    pub fn export_history(&self) -> String {
+        self.history.export()
+    }
+
+    /// Replace the undo/redo history with the output of `exportHistory`
+    /// Every undo entry must unwind from the current content and every redo entry
+    /// must reapply, otherwise an error message is thrown and the history is kept
+    #[wasm_bindgen(js_name = importHistory)]
+    pub fn import_history(&mut self, json: &str) -> Result<(), JsValue> {
+        self.import_history_json(json).map_err(|message| JsValue::from_str(&message))
+    }
+
     /// Get the full text content of the document
+    /// Every call copies the whole document into a new string and again across
+    /// the WASM boundary; prefer `getTextSlice` or `getLines` for partial reads
     #[wasm_bindgen(js_name = getText)]
     pub fn get_text(&self) -> String {
         self.piece_table.get_text()
     }
 
-    /// Get the total length of the document in characters
+    /// Get the total length of the document in bytes
     #[wasm_bindgen(js_name = getLength)]
     pub fn get_length(&self) -> usize {
         self.piece_table.get_length()
     }
 
+    /// Create a cursor that reads characters lazily starting at an offset
+    /// The cursor is invalidated by any subsequent edit
+    #[wasm_bindgen(js_name = charCursor)]
+    pub fn char_cursor(&self, offset: usize) -> CharCursor {
+        CharCursor::new(self, offset)
+    }
+
     /// Get the number of lines in the document
     #[wasm_bindgen(js_name = getLineCount)]
     pub fn get_line_count(&self) -> usize {
@@ -52,16 +159,200 @@ impl Document {
         self.piece_table.get_line(line)
     }
 
-    /// Get the character offset at the start of a line
+    /// Get the number of characters in a line, excluding the newline, without copying it
+    /// Returns None if the line doesn't exist
+    #[wasm_bindgen(js_name = getLineLength)]
+    pub fn get_line_length(&self, line: usize) -> Option<usize> {
+        self.piece_table.line_char_length(line)
+    }
+
+    /// Get the length in bytes of a line, excluding the newline
+    /// Returns None if the line doesn't exist
+    #[wasm_bindgen(js_name = getLineByteLength)]
+    pub fn get_line_byte_length(&self, line: usize) -> Option<usize> {
+        self.piece_table.line_byte_length(line)
+    }
+
+    /// Get the lines in `start_line..end_line` as an array of strings in one pass
+    /// `end_line` is exclusive and clamped to the line count
+    #[wasm_bindgen(js_name = getLines)]
+    pub fn get_lines(&self, start_line: usize, end_line: usize) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.piece_table.get_lines(start_line, end_line)).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get the offset after the next word or punctuation run, skipping whitespace
+    /// Offsets inside a character are snapped back to its start
+    #[wasm_bindgen(js_name = nextWordBoundary)]
+    pub fn next_word_boundary(&self, offset: usize) -> usize {
+        let offset = self.piece_table.floor_char_boundary(offset);
+        word::next_boundary(self.piece_table.text(), offset)
+    }
+
+    /// Get the offset at the start of the previous word or punctuation run
+    #[wasm_bindgen(js_name = prevWordBoundary)]
+    pub fn prev_word_boundary(&self, offset: usize) -> usize {
+        let offset = self.piece_table.floor_char_boundary(offset);
+        word::prev_boundary(self.piece_table.text(), offset)
+    }
+
+    /// Get the offset after the grapheme cluster at an offset, for moving right
+    /// A ZWJ emoji sequence or a letter with combining marks is stepped over whole
+    #[wasm_bindgen(js_name = nextGraphemeBoundary)]
+    pub fn next_grapheme_boundary(&self, offset: usize) -> usize {
+        let offset = self.piece_table.floor_char_boundary(offset);
+        grapheme::next_boundary(self.piece_table.text(), offset)
+    }
+
+    /// Get the offset at the start of the grapheme cluster before an offset, for moving left
+    #[wasm_bindgen(js_name = prevGraphemeBoundary)]
+    pub fn prev_grapheme_boundary(&self, offset: usize) -> usize {
+        let offset = self.piece_table.floor_char_boundary(offset);
+        grapheme::prev_boundary(self.piece_table.text(), offset)
+    }
+
+    /// Convert a byte offset to a position whose column counts grapheme clusters
+    /// An offset inside a cluster reports the column of that cluster's start
+    #[wasm_bindgen(js_name = positionGrapheme)]
+    pub fn position_grapheme(&self, offset: usize) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.grapheme_position(offset)).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get the range of the word, whitespace or punctuation run at an offset
+    #[wasm_bindgen(js_name = wordRangeAt)]
+    pub fn word_range_at(&self, offset: usize) -> JsValue {
+        let range = self.word_range(offset);
+        serde_wasm_bindgen::to_value(&range).unwrap_or(JsValue::NULL)
+    }
+
+    /// Find the nearest preceding line with strictly less indentation
+    /// Blank lines are skipped; returns None for a blank line or a top-level line
+    #[wasm_bindgen(js_name = parentLine)]
+    pub fn parent_line(&self, line: usize) -> Option<usize> {
+        let indent = Self::indent_width(&self.piece_table.get_line(line)?)?;
+        (0..line).rev().find(|&candidate| {
+            self.piece_table
+                .get_line(candidate)
+                .and_then(|text| Self::indent_width(&text))
+                .is_some_and(|candidate_indent| candidate_indent < indent)
+        })
+    }
+
+    /// Find the nearest line after `line` that isn't empty or whitespace-only
+    #[wasm_bindgen(js_name = nextNonBlankLine)]
+    pub fn next_non_blank_line(&self, line: usize) -> Option<usize> {
+        (line + 1..self.piece_table.get_line_count()).find(|&candidate| !self.is_blank_line(candidate))
+    }
+
+    /// Find the nearest line before `line` that isn't empty or whitespace-only
+    #[wasm_bindgen(js_name = prevNonBlankLine)]
+    pub fn prev_non_blank_line(&self, line: usize) -> Option<usize> {
+        let end = line.min(self.piece_table.get_line_count());
+        (0..end).rev().find(|&candidate| !self.is_blank_line(candidate))
+    }
+
+    /// Get the character length of every line, excluding newlines, in a single pass
+    #[wasm_bindgen(js_name = lineLengths)]
+    pub fn line_lengths(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.piece_table.line_lengths()).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get the byte offset at the start of a line
     #[wasm_bindgen(js_name = getLineOffset)]
     pub fn get_line_offset(&self, line: usize) -> Option<usize> {
         self.piece_table.get_line_offset(line)
     }
 
-    /// Insert text at the specified offset
+    /// Get the length of the common prefix shared with another document
+    #[wasm_bindgen(js_name = commonPrefixLen)]
+    pub fn common_prefix_len(&self, other: &Document) -> usize {
+        self.piece_table.common_prefix_len(&other.piece_table)
+    }
+
+    /// Get the length of the common suffix shared with another document
+    /// The prefix and suffix are measured independently and may overlap
+    #[wasm_bindgen(js_name = commonSuffixLen)]
+    pub fn common_suffix_len(&self, other: &Document) -> usize {
+        self.piece_table.common_suffix_len(&other.piece_table)
+    }
+
+    /// Get the length of the document in UTF-16 code units, like `string.length` in JS
+    #[wasm_bindgen(js_name = getLengthUtf16)]
+    pub fn get_length_utf16(&self) -> usize {
+        self.piece_table.utf16_length()
+    }
+
+    /// Insert text at a UTF-16 code unit offset
+    #[wasm_bindgen(js_name = insertUtf16)]
+    pub fn insert_utf16(&mut self, utf16_offset: usize, text: &str) -> bool {
+        match self.piece_table.utf16_to_offset(utf16_offset) {
+            Some(offset) => self.insert(offset, text),
+            None => false,
+        }
+    }
+
+    /// Delete `utf16_length` UTF-16 code units at a UTF-16 code unit offset
+    #[wasm_bindgen(js_name = deleteUtf16)]
+    pub fn delete_utf16(&mut self, utf16_offset: usize, utf16_length: usize) -> bool {
+        let Some(utf16_end) = utf16_offset.checked_add(utf16_length) else {
+            return false;
+        };
+        match (self.piece_table.utf16_to_offset(utf16_offset), self.piece_table.utf16_to_offset(utf16_end)) {
+            (Some(start), Some(end)) => self.delete(start, end - start),
+            _ => false,
+        }
+    }
+
+    /// Convert a UTF-16 code unit offset to a position with a UTF-16 column
+    /// Returns null if the offset is out of range or inside a surrogate pair
+    #[wasm_bindgen(js_name = offsetToPositionUtf16)]
+    pub fn offset_to_position_utf16(&self, utf16_offset: usize) -> JsValue {
+        match self.utf16_position_at(utf16_offset) {
+            Some(position) => serde_wasm_bindgen::to_value(&position).unwrap_or(JsValue::NULL),
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Convert a position with a UTF-16 column to a UTF-16 code unit offset
+    /// Columns past the line end are clamped like `positionToOffset`
+    #[wasm_bindgen(js_name = positionToOffsetUtf16)]
+    pub fn position_to_offset_utf16(&self, line: usize, utf16_column: usize) -> Option<usize> {
+        let line_offset = self.piece_table.get_line_offset(line)?;
+        let line_text = self.piece_table.get_line(line)?;
+
+        let mut units = 0;
+        for c in line_text.chars() {
+            if units + c.len_utf16() > utf16_column {
+                break;
+            }
+            units += c.len_utf16();
+        }
+        Some(self.piece_table.offset_to_utf16(line_offset) + units)
+    }
+
+    /// Get the number of UTF-8 bytes covered by a range given in UTF-16 code units
+    /// Returns None if either end is out of range or splits a surrogate pair
+    #[wasm_bindgen(js_name = utf16RangeByteLength)]
+    pub fn utf16_range_byte_length(&self, utf16_start: usize, utf16_end: usize) -> Option<usize> {
+        if utf16_start > utf16_end {
+            return None;
+        }
+        let start = self.piece_table.utf16_to_offset(utf16_start)?;
+        let end = self.piece_table.utf16_to_offset(utf16_end)?;
+        Some(end - start)
+    }
+
+    /// Get the number of characters (not bytes) before the start of a line
+    #[wasm_bindgen(js_name = charOffsetOfLine)]
+    pub fn char_offset_of_line(&self, line: usize) -> Option<usize> {
+        self.piece_table.char_offset_of_line(line)
+    }
+
+    /// Insert text at the specified byte offset
+    /// Returns false if the offset is past the end or inside a character, or the
+    /// document is read-only
     #[wasm_bindgen]
     pub fn insert(&mut self, offset: usize, text: &str) -> bool {
-        if offset > self.piece_table.get_length() {
+        if self.read_only || !self.piece_table.is_valid_offset(offset) {
             return false;
         }
 
@@ -72,15 +363,27 @@ impl Document {
             text.to_string(),
         );
 
-        self.piece_table.insert(offset, text);
+        self.splice(offset, 0, text);
         self.history.push(operation);
         true
     }
 
-    /// Delete text at the specified offset with the given length
+    /// Append text at the end of the document and return the range it occupies
+    /// Returns null if the document is read-only
+    #[wasm_bindgen(js_name = appendReturningRange)]
+    pub fn append_returning_range(&mut self, text: &str) -> JsValue {
+        match self.append_with_range(text) {
+            Some(range) => serde_wasm_bindgen::to_value(&range).unwrap_or(JsValue::NULL),
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Delete `length` bytes at the specified byte offset
+    /// Returns false if the range is out of bounds or either end is inside a
+    /// character, or the document is read-only
     #[wasm_bindgen]
     pub fn delete(&mut self, offset: usize, length: usize) -> bool {
-        if offset + length > self.piece_table.get_length() {
+        if self.read_only || !self.piece_table.is_valid_range(offset, length) {
             return false;
         }
 
@@ -92,30 +395,374 @@ impl Document {
             deleted_text,
         );
 
-        self.piece_table.delete(offset, length);
+        self.splice(offset, length, "");
         self.history.push(operation);
         true
     }
 
-    /// Replace text at the specified range
+    /// Replace `length` bytes at the specified byte offset with new text
+    /// Returns false if the range is out of bounds or either end is inside a
+    /// character, or the document is read-only
     #[wasm_bindgen]
     pub fn replace(&mut self, offset: usize, length: usize, text: &str) -> bool {
-        if offset + length > self.piece_table.get_length() {
+        if self.read_only || !self.piece_table.is_valid_range(offset, length) {
             return false;
         }
 
         let deleted_text = self.piece_table.get_text_range(offset, length);
         let operation = Operation::new_replace(offset, length, deleted_text, text.to_string());
 
-        self.piece_table.delete(offset, length);
-        self.piece_table.insert(offset, text);
+        self.splice(offset, length, text);
+        self.history.push(operation);
+        true
+    }
+
+    /// Delete a line including its line break as one operation
+    /// The last line takes the preceding line break with it instead
+    #[wasm_bindgen(js_name = deleteLine)]
+    pub fn delete_line(&mut self, line: usize) -> bool {
+        let Some(start) = self.piece_table.get_line_offset(line) else {
+            return false;
+        };
+        match self.piece_table.get_line_offset(line + 1) {
+            Some(next) => self.delete(start, next - start),
+            None => {
+                let start = start.saturating_sub(1);
+                self.delete(start, self.piece_table.get_length() - start)
+            }
+        }
+    }
+
+    /// Insert a copy of a line directly below it as one operation
+    #[wasm_bindgen(js_name = duplicateLine)]
+    pub fn duplicate_line(&mut self, line: usize) -> bool {
+        let Some(text) = self.piece_table.get_line(line) else {
+            return false;
+        };
+        match self.piece_table.get_line_offset(line + 1) {
+            Some(next) => self.insert(next, &format!("{}\n", text)),
+            None => self.insert(self.piece_table.get_length(), &format!("\n{}", text)),
+        }
+    }
+
+    /// Swap a line with the one above (negative `direction`) or below (positive)
+    /// Recorded as one operation; returns false if there is no line to swap with
+    #[wasm_bindgen(js_name = moveLine)]
+    pub fn move_line(&mut self, line: usize, direction: i32) -> bool {
+        let upper = match direction.signum() {
+            -1 if line > 0 => line - 1,
+            1 => line,
+            _ => return false,
+        };
+        let (Some(first), Some(second)) = (self.piece_table.get_line(upper), self.piece_table.get_line(upper + 1)) else {
+            return false;
+        };
+        let Some(start) = self.piece_table.get_line_offset(upper) else {
+            return false;
+        };
+        let length = first.len() + 1 + second.len();
+        self.replace(start, length, &format!("{}\n{}", second, first))
+    }
+
+    /// Remove trailing spaces and tabs from a single line as one operation
+    /// A trailing carriage return is kept; returns whether anything was removed
+    #[wasm_bindgen(js_name = trimLineTrailing)]
+    pub fn trim_line_trailing(&mut self, line: usize) -> bool {
+        let (Some(text), Some(line_offset)) = (self.piece_table.get_line(line), self.piece_table.get_line_offset(line)) else {
+            return false;
+        };
+        let (body_length, trailing) = Self::trailing_whitespace(&text);
+        if trailing == 0 {
+            return false;
+        }
+        self.delete(line_offset + body_length - trailing, trailing)
+    }
+
+    /// Remove trailing spaces and tabs from every line as one operation
+    /// Blank lines stay as empty lines, carriage returns and the presence of a
+    /// final newline are kept; returns the number of lines changed
+    #[wasm_bindgen(js_name = trimTrailingWhitespace)]
+    pub fn trim_trailing_whitespace(&mut self) -> usize {
+        if self.read_only {
+            return 0;
+        }
+        let text = self.piece_table.get_text();
+
+        let mut changed = 0;
+        let lines: Vec<String> = text
+            .split('\n')
+            .map(|line| {
+                let (body_length, trailing) = Self::trailing_whitespace(line);
+                if trailing == 0 {
+                    return line.to_string();
+                }
+                changed += 1;
+                format!("{}{}", &line[..body_length - trailing], &line[body_length..])
+            })
+            .collect();
+
+        if changed > 0 {
+            self.replace_changed_region(&lines.join("\n"));
+        }
+        changed
+    }
+
+    /// Replace a range only if it still contains `expected_old`
+    /// Returns false without mutating if the range is invalid or its text differs
+    #[wasm_bindgen(js_name = compareAndReplace)]
+    pub fn compare_and_replace(&mut self, offset: usize, length: usize, expected_old: &str, new_text: &str) -> bool {
+        if length != expected_old.len() || self.get_text_range(offset, length).as_deref() != Some(expected_old) {
+            return false;
+        }
+        self.replace(offset, length, new_text)
+    }
+
+    /// Replace all content with `content` as a single undoable operation
+    /// The piece table is rebuilt from scratch so earlier fragmentation is gone;
+    /// states captured with `captureState` are released. Returns false if read-only
+    #[wasm_bindgen(js_name = setText)]
+    pub fn set_text(&mut self, content: &str) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let old_text = self.piece_table.get_text();
+        let old_length = old_text.len();
+        let operation = Operation::new_replace(0, old_length, old_text, content.to_string());
+
+        let old_lines = self.piece_table.get_line_count();
+        self.states.clear();
+        self.piece_table.reset(content.to_string());
+        self.after_change(0, old_length, content, old_lines);
         self.history.push(operation);
         true
     }
 
+    /// Empty the document as a single undoable delete, keeping this handle usable
+    /// The piece table is rebuilt empty and states captured with `captureState`
+    /// are released. Returns false if the document is already empty or read-only
+    #[wasm_bindgen]
+    pub fn clear(&mut self) -> bool {
+        if self.read_only || self.piece_table.get_length() == 0 {
+            return false;
+        }
+        let old_text = self.piece_table.get_text();
+        let old_length = old_text.len();
+        let old_lines = self.piece_table.get_line_count();
+
+        self.states.clear();
+        self.piece_table.reset(String::new());
+        self.after_change(0, old_length, "", old_lines);
+        self.history.push(Operation::delete(0, old_text));
+        true
+    }
+
+    /// Compute line-level insert and delete operations turning this document into `other_text`
+    /// The operations apply in order, as `applyDiff` does; unchanged lines are not touched
+    #[wasm_bindgen]
+    pub fn diff(&self, other_text: &str) -> JsValue {
+        let operations = diff::line_diff(self.piece_table.text(), other_text);
+        serde_wasm_bindgen::to_value(&operations).unwrap_or(JsValue::NULL)
+    }
+
+    /// Apply operations produced by `diff` in order as a single undoable step
+    /// Nothing is applied unless every operation fits the document and each
+    /// deleted or replaced text matches what is there
+    #[wasm_bindgen(js_name = applyDiff)]
+    pub fn apply_diff(&mut self, operations: JsValue) -> bool {
+        match serde_wasm_bindgen::from_value::<Vec<Operation>>(operations) {
+            Ok(operations) => self.apply_operations(&operations),
+            Err(_) => false,
+        }
+    }
+
+    /// Apply `{ offset, length, text }` edits, all computed against the current
+    /// content, as a single undoable step
+    /// The edits may be given in any order and are applied back to front. Returns
+    /// false if the document is read-only; throws `{ kind, message, index }` if
+    /// an edit is out of range or two edits overlap, leaving the document untouched
+    #[wasm_bindgen(js_name = applyEdits)]
+    pub fn apply_edits(&mut self, edits: JsValue) -> Result<bool, JsValue> {
+        let edits: Vec<TextEdit> = serde_wasm_bindgen::from_value(edits).map_err(|error| {
+            let error = EditError::new(EditErrorKind::InvalidInput, error.to_string(), None);
+            serde_wasm_bindgen::to_value(&error).unwrap_or(JsValue::NULL)
+        })?;
+        self.apply_text_edits(&edits)
+            .map_err(|error| serde_wasm_bindgen::to_value(&error).unwrap_or(JsValue::NULL))
+    }
+
+    /// Replace the whole content with freshly loaded text as one operation
+    /// Returns false without touching content or history if the text is unchanged;
+    /// otherwise the history is optionally cleared after the replace
+    #[wasm_bindgen]
+    pub fn reload(&mut self, text: &str, clear_history: bool) -> bool {
+        let length = self.piece_table.get_length();
+        if self.read_only || (length == text.len() && self.piece_table.text() == text) {
+            return false;
+        }
+
+        self.replace(0, length, text);
+        if clear_history {
+            self.history.clear();
+        }
+        true
+    }
+
+    /// Normalize indentation to `spaces_per_level` spaces per level as one operation
+    /// The current level comes from the detected indent unit, snapping inconsistent
+    /// lines to the nearest level; returns the number of lines changed
+    #[wasm_bindgen(js_name = reindentToUnit)]
+    pub fn reindent_to_unit(&mut self, spaces_per_level: usize) -> usize {
+        if self.read_only {
+            return 0;
+        }
+        let text = self.piece_table.get_text();
+        let unit = indent::detect_indent_unit(text.split('\n')).unwrap_or(spaces_per_level);
+
+        let mut changed = 0;
+        let lines: Vec<String> = text
+            .split('\n')
+            .map(|line| {
+                let reindented = indent::reindent_line(line, unit, spaces_per_level);
+                if reindented != line {
+                    changed += 1;
+                }
+                reindented
+            })
+            .collect();
+
+        if changed > 0 {
+            self.replace_changed_region(&lines.join("\n"));
+        }
+        changed
+    }
+
+    /// Wrap a range in block comment delimiters, or unwrap it if already commented
+    /// The delimiters may be inside the range or immediately surrounding it
+    #[wasm_bindgen(js_name = blockComment)]
+    pub fn block_comment(&mut self, start_offset: usize, end_offset: usize, open: &str, close: &str) -> bool {
+        if start_offset > end_offset
+            || !self.piece_table.is_valid_range(start_offset, end_offset - start_offset)
+            || open.is_empty()
+            || close.is_empty()
+        {
+            return false;
+        }
+
+        let selected = self.piece_table.get_text_range(start_offset, end_offset - start_offset);
+
+        if selected.len() >= open.len() + close.len() && selected.starts_with(open) && selected.ends_with(close) {
+            let inner = &selected[open.len()..selected.len() - close.len()];
+            return self.replace(start_offset, selected.len(), inner);
+        }
+
+        if start_offset >= open.len() && end_offset + close.len() <= self.piece_table.get_length() {
+            let before = self.piece_table.get_text_range(start_offset - open.len(), open.len());
+            let after = self.piece_table.get_text_range(end_offset, close.len());
+            if before == open && after == close {
+                let length = open.len() + selected.len() + close.len();
+                return self.replace(start_offset - open.len(), length, &selected);
+            }
+        }
+
+        let wrapped = format!("{}{}{}", open, selected, close);
+        self.replace(start_offset, selected.len(), &wrapped)
+    }
+
+    /// Find the first match of `query` at or after a byte offset
+    /// `options` is `{ caseSensitive, wholeWord }` (both default to false) and may be
+    /// omitted. Matches may span piece boundaries; returns a range of positions or null
+    #[wasm_bindgen]
+    pub fn find(&self, query: &str, start_offset: usize, options: JsValue) -> JsValue {
+        let options = Self::search_options(options);
+        match self.find_range(query, start_offset, options) {
+            Some(range) => serde_wasm_bindgen::to_value(&range).unwrap_or(JsValue::NULL),
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Find the last match of `query` that ends at or before `start_offset`, for
+    /// find-previous; takes the same options as `find`
+    /// Returns the match as a position range, or null if there is none
+    #[wasm_bindgen(js_name = findPrev)]
+    pub fn find_prev(&self, query: &str, start_offset: usize, options: JsValue) -> JsValue {
+        let options = Self::search_options(options);
+        match self.find_prev_range(query, start_offset, options) {
+            Some(range) => serde_wasm_bindgen::to_value(&range).unwrap_or(JsValue::NULL),
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Find every non-overlapping match of `query` as an array of position ranges
+    #[wasm_bindgen(js_name = findAll)]
+    pub fn find_all(&self, query: &str, options: JsValue) -> JsValue {
+        let ranges = self.find_all_ranges(query, Self::search_options(options));
+        serde_wasm_bindgen::to_value(&ranges).unwrap_or(JsValue::NULL)
+    }
+
+    /// Create a cursor that yields the matches of `query` lazily, in document order
+    /// Accepts the same options as `findAll`; the cursor is invalidated by any
+    /// subsequent edit
+    #[wasm_bindgen(js_name = searchCursor)]
+    pub fn search_cursor(&self, query: &str, options: JsValue) -> SearchCursor {
+        self.search_cursor_with(query, Self::search_options(options))
+    }
+
+    /// Find every match of a regex as `{ range, groups }` objects
+    /// `groups` holds one range per capture group, or null for groups that didn't
+    /// participate. Throws `{ kind, message }` if the pattern or flags are invalid
+    #[wasm_bindgen(js_name = findAllRegex)]
+    pub fn find_all_regex(&self, pattern: &str, flags: Option<String>) -> Result<JsValue, JsValue> {
+        let matches = self
+            .regex_match_ranges(pattern, flags.as_deref().unwrap_or_default())
+            .map_err(|error| serde_wasm_bindgen::to_value(&error).unwrap_or(JsValue::NULL))?;
+        Ok(serde_wasm_bindgen::to_value(&matches).unwrap_or(JsValue::NULL))
+    }
+
+    /// Replace every regex match, expanding `$1`-style references, as one undoable step
+    /// Returns the number of replacements; throws `{ kind, message }` if the
+    /// pattern or flags are invalid
+    #[wasm_bindgen(js_name = replaceAllRegex)]
+    pub fn replace_all_regex(&mut self, pattern: &str, replacement: &str, flags: Option<String>) -> Result<usize, JsValue> {
+        self.replace_all_regex_matches(pattern, replacement, flags.as_deref().unwrap_or_default())
+            .map_err(|error| serde_wasm_bindgen::to_value(&error).unwrap_or(JsValue::NULL))
+    }
+
+    /// Replace every whole-word occurrence of `word` as a single undo step
+    /// Occurrences embedded in longer words are left untouched; returns the number replaced
+    #[wasm_bindgen(js_name = replaceAllWholeWord)]
+    pub fn replace_all_whole_word(&mut self, word: &str, replacement: &str, case_sensitive: bool) -> usize {
+        let options = SearchOptions { case_sensitive, whole_word: true };
+        self.replace_all_matches(word, replacement, options).len()
+    }
+
+    /// Replace every non-overlapping match of `query` as a single undo step
+    /// Takes the same options as `findAll` and returns the ranges of the inserted
+    /// replacements in the updated document, so the caller can highlight them
+    #[wasm_bindgen(js_name = replaceAll)]
+    pub fn replace_all(&mut self, query: &str, replacement: &str, options: JsValue) -> JsValue {
+        let ranges = self.replace_all_matches(query, replacement, Self::search_options(options));
+        serde_wasm_bindgen::to_value(&ranges).unwrap_or(JsValue::NULL)
+    }
+
+    /// Make the document read-only, turning every content mutation into a no-op
+    /// Mutators return false (or 0 for counts) while set; reads are unaffected
+    #[wasm_bindgen(js_name = setReadOnly)]
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Check if the document is read-only
+    #[wasm_bindgen(js_name = isReadOnly)]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     /// Undo the last operation
     #[wasm_bindgen]
     pub fn undo(&mut self) -> bool {
+        if self.read_only {
+            return false;
+        }
         if let Some(operation) = self.history.undo() {
             self.apply_inverse_operation(&operation);
             true
@@ -127,6 +774,9 @@ impl Document {
     /// Redo the last undone operation
     #[wasm_bindgen]
     pub fn redo(&mut self) -> bool {
+        if self.read_only {
+            return false;
+        }
         if let Some(operation) = self.history.redo() {
             self.apply_operation(&operation);
             true
@@ -135,6 +785,21 @@ impl Document {
         }
     }
 
+    /// Start grouping edits so a single `undo` reverts all of them
+    /// Calls may be nested; the group is recorded when the outermost one ends.
+    /// Undo and redo are unavailable while a transaction is open
+    #[wasm_bindgen(js_name = beginTransaction)]
+    pub fn begin_transaction(&mut self) {
+        self.history.begin_transaction();
+    }
+
+    /// Close the innermost transaction started by `beginTransaction`
+    /// Returns false if no transaction is open
+    #[wasm_bindgen(js_name = endTransaction)]
+    pub fn end_transaction(&mut self) -> bool {
+        self.history.end_transaction()
+    }
+
     /// Check if undo is available
     #[wasm_bindgen(js_name = canUndo)]
     pub fn can_undo(&self) -> bool {
@@ -147,69 +812,2300 @@ impl Document {
         self.history.can_redo()
     }
 
-    /// Clear the undo/redo history
-    #[wasm_bindgen(js_name = clearHistory)]
-    pub fn clear_history(&mut self) {
-        self.history.clear();
+    /// Get the number of steps that can be undone
+    #[wasm_bindgen(js_name = undoDepth)]
+    pub fn undo_depth(&self) -> usize {
+        self.history.undo_count()
     }
 
-    /// Get text in a specific range
-    #[wasm_bindgen(js_name = getTextRange)]
-    pub fn get_text_range(&self, offset: usize, length: usize) -> Option<String> {
-        if offset + length > self.piece_table.get_length() {
-            return None;
+    /// Get the number of steps that can be redone
+    #[wasm_bindgen(js_name = redoDepth)]
+    pub fn redo_depth(&self) -> usize {
+        self.history.redo_count()
+    }
+
+    /// Describe the step `undo` would revert as `{ opType, offset, length }`
+    /// Returns null if there is nothing to undo
+    #[wasm_bindgen(js_name = peekUndo)]
+    pub fn peek_undo(&self) -> JsValue {
+        match self.history.peek_undo().map(OperationSummary::from) {
+            Some(summary) => serde_wasm_bindgen::to_value(&summary).unwrap_or(JsValue::NULL),
+            None => JsValue::NULL,
         }
-        Some(self.piece_table.get_text_range(offset, length))
     }
 
-    /// Convert a character offset to a position (line, column)
-    #[wasm_bindgen(js_name = offsetToPosition)]
-    pub fn offset_to_position(&self, offset: usize) -> JsValue {
-        let position = self.piece_table.offset_to_position(offset);
-        serde_wasm_bindgen::to_value(&position).unwrap_or(JsValue::NULL)
+    /// Describe the step `redo` would reapply as `{ opType, offset, length }`
+    /// Returns null if there is nothing to redo
+    #[wasm_bindgen(js_name = peekRedo)]
+    pub fn peek_redo(&self) -> JsValue {
+        match self.history.peek_redo().map(OperationSummary::from) {
+            Some(summary) => serde_wasm_bindgen::to_value(&summary).unwrap_or(JsValue::NULL),
+            None => JsValue::NULL,
+        }
     }
 
-    /// Convert a position (line, column) to a character offset
-    #[wasm_bindgen(js_name = positionToOffset)]
-    pub fn position_to_offset(&self, line: usize, column: usize) -> Option<usize> {
-        self.piece_table.position_to_offset(line, column)
+    /// Set how many undo steps are kept, with 0 meaning unlimited (default 1000)
+    /// Lowering the limit drops the oldest steps immediately
+    #[wasm_bindgen(js_name = setHistoryLimit)]
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
     }
 
-    fn apply_operation(&mut self, operation: &Operation) {
-        match operation.op_type {
-            OperationType::Insert => {
-                self.piece_table.insert(operation.offset, &operation.text);
-            }
-            OperationType::Delete => {
-                self.piece_table.delete(operation.offset, operation.length);
-            }
-            OperationType::Replace => {
-                self.piece_table.delete(operation.offset, operation.old_text.as_ref().map_or(0, |t| t.len()));
-                self.piece_table.insert(operation.offset, &operation.text);
-            }
+    /// Keep the redo stack when an edit exactly repeats the next redo entry
+    /// Retyping what was just undone then behaves like redo instead of discarding redo history
+    #[wasm_bindgen(js_name = setPreserveMatchingRedo)]
+    pub fn set_preserve_matching_redo(&mut self, enabled: bool) {
+        self.history.set_preserve_matching_redo(enabled);
+    }
+
+    /// Export the undoable history, oldest first, as portable patch entries
+    /// Each entry has the shape `{ op, offset, unit, removed, inserted }` with byte offsets
+    #[wasm_bindgen(js_name = getHistoryAsJsonPatch)]
+    pub fn get_history_as_json_patch(&self) -> JsValue {
+        let entries = patch::from_operations(self.history.undo_operations());
+        serde_wasm_bindgen::to_value(&entries).unwrap_or(JsValue::NULL)
+    }
+
+    /// Apply patch entries in order, as produced by `getHistoryAsJsonPatch`
+    /// Nothing is applied unless every entry's removed text matches the document;
+    /// the whole patch undoes as a single step
+    #[wasm_bindgen(js_name = applyJsonPatch)]
+    pub fn apply_json_patch(&mut self, patch: JsValue) -> bool {
+        match serde_wasm_bindgen::from_value::<Vec<PatchEntry>>(patch) {
+            Ok(entries) => self.apply_patch_entries(&entries),
+            Err(_) => false,
         }
     }
 
-    fn apply_inverse_operation(&mut self, operation: &Operation) {
-        match operation.op_type {
-            OperationType::Insert => {
-                self.piece_table.delete(operation.offset, operation.length);
-            }
-            OperationType::Delete => {
-                self.piece_table.insert(operation.offset, &operation.text);
+    /// Capture the current content and return an id for `restoreState`
+    /// Captures share the document's buffers and only copy the piece list
+    #[wasm_bindgen(js_name = captureState)]
+    pub fn capture_state(&mut self) -> u32 {
+        let id = self.next_state_id;
+        self.next_state_id += 1;
+        self.states.insert(id, self.piece_table.snapshot());
+        id
+    }
+
+    /// Restore the content captured by `captureState` as a single undoable operation
+    /// Returns false if the state id is unknown
+    #[wasm_bindgen(js_name = restoreState)]
+    pub fn restore_state(&mut self, state_id: u32) -> bool {
+        let Some(snapshot) = self.states.get(&state_id) else {
+            return false;
+        };
+        if self.read_only {
+            return false;
+        }
+        let text = self.piece_table.snapshot_text(snapshot);
+        self.replace_changed_region(&text);
+        true
+    }
+
+    /// Drop inserted text that is no longer part of the document to reclaim memory
+    /// Content and undo/redo history are unaffected, but states captured with
+    /// `captureState` are released. Returns the number of bytes reclaimed
+    #[wasm_bindgen]
+    pub fn compact(&mut self) -> usize {
+        self.states.clear();
+        self.piece_table.compact()
+    }
+
+    /// Forget a captured state to free its piece list
+    #[wasm_bindgen(js_name = releaseState)]
+    pub fn release_state(&mut self, state_id: u32) -> bool {
+        self.states.remove(&state_id).is_some()
+    }
+
+    /// Create a marker at a byte offset that shifts as text is edited around it
+    /// `bias` decides whether text inserted exactly at the marker goes after it
+    /// (`Left`) or before it (`Right`). Returns None if the offset is invalid
+    #[wasm_bindgen(js_name = createMarker)]
+    pub fn create_marker(&mut self, offset: usize, bias: MarkerBias) -> Option<u32> {
+        if !self.piece_table.is_valid_offset(offset) {
+            return None;
+        }
+        let id = self.next_marker_id;
+        self.next_marker_id += 1;
+        self.markers.insert(id, Marker::new(offset, bias));
+        Some(id)
+    }
+
+    /// Get the current byte offset of a marker
+    #[wasm_bindgen(js_name = getMarkerOffset)]
+    pub fn get_marker_offset(&self, marker_id: u32) -> Option<usize> {
+        self.markers.get(&marker_id).map(|marker| marker.offset)
+    }
+
+    /// Get the current text between two markers, in document order
+    /// Returns null if either marker was removed
+    #[wasm_bindgen(js_name = textBetweenMarkers)]
+    pub fn text_between_markers(&self, a: u32, b: u32) -> Option<String> {
+        let a = self.get_marker_offset(a)?;
+        let b = self.get_marker_offset(b)?;
+        let (start, end) = (a.min(b), a.max(b));
+        self.get_text_range(start, end - start)
+    }
+
+    /// Stop tracking a marker
+    #[wasm_bindgen(js_name = removeMarker)]
+    pub fn remove_marker(&mut self, marker_id: u32) -> bool {
+        self.markers.remove(&marker_id).is_some()
+    }
+
+    /// Merge the two most recent edits into a single undo step
+    /// Returns false if fewer than two edits can be undone
+    #[wasm_bindgen(js_name = mergeLastTwoEdits)]
+    pub fn merge_last_two_edits(&mut self) -> bool {
+        self.history.merge_last_two()
+    }
+
+    /// Tag the current point in the history with a name, e.g. on save
+    /// A checkpoint with the same name is replaced. Returns false while a
+    /// transaction is open
+    #[wasm_bindgen]
+    pub fn checkpoint(&mut self, name: &str) -> bool {
+        self.history.checkpoint(name)
+    }
+
+    /// Undo or redo until the history is back at a checkpoint
+    /// Returns false, leaving the document untouched, if the checkpoint doesn't
+    /// exist or is stale because a later edit, eviction or merge made it unreachable
+    #[wasm_bindgen(js_name = revertToCheckpoint)]
+    pub fn revert_to_checkpoint(&mut self, name: &str) -> bool {
+        let Some(target) = self.history.get_checkpoint(name).filter(|c| !c.stale).map(|c| c.depth) else {
+            return false;
+        };
+        if self.read_only || self.history.in_transaction() {
+            return false;
+        }
+        while self.history.depth() > target {
+            if !self.undo() {
+                return false;
             }
-            OperationType::Replace => {
-                self.piece_table.delete(operation.offset, operation.text.len());
-                if let Some(old_text) = &operation.old_text {
-                    self.piece_table.insert(operation.offset, old_text);
-                }
+        }
+        while self.history.depth() < target {
+            if !self.redo() {
+                return false;
             }
         }
+        true
     }
-}
 
-/// Initialize the WASM module
-#[wasm_bindgen(start)]
-pub fn init() {
-    // WASM module initialization
+    /// List checkpoints in creation order as `{ name, depth, stale }`
+    #[wasm_bindgen(js_name = listCheckpoints)]
+    pub fn list_checkpoints(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self.history.checkpoints()).unwrap_or(JsValue::NULL)
+    }
+
+    /// Remove a checkpoint, returning false if there was none with that name
+    #[wasm_bindgen(js_name = removeCheckpoint)]
+    pub fn remove_checkpoint(&mut self, name: &str) -> bool {
+        self.history.remove_checkpoint(name)
+    }
+
+    /// Clear the undo/redo history
+    #[wasm_bindgen(js_name = clearHistory)]
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// Summarize the content in one scan: `{ isText, hasBom, hasCrlf, hasTabs }`
+    #[wasm_bindgen(js_name = looksLikeText)]
+    pub fn looks_like_text(&self) -> JsValue {
+        let result = analysis::characteristics(self.piece_table.chunks());
+        serde_wasm_bindgen::to_value(&result).unwrap_or(JsValue::NULL)
+    }
+
+    /// Report load-time anomalies in one scan:
+    /// `{ validUtf8, lineEnding, mixedLineEndings, hasBom, hasFinalNewline }`
+    /// `lineEnding` is the dominant style: "lf", "crlf", "cr", or "none" without line breaks
+    #[wasm_bindgen]
+    pub fn analyze(&self) -> JsValue {
+        let report = analysis::report(self.piece_table.chunks());
+        serde_wasm_bindgen::to_value(&report).unwrap_or(JsValue::NULL)
+    }
+
+    /// Report on raw file bytes before they are decoded into a document
+    /// Same shape as `analyze`, with `validUtf8` false if the bytes aren't UTF-8
+    #[wasm_bindgen(js_name = analyzeBytes)]
+    pub fn analyze_bytes(bytes: &[u8]) -> JsValue {
+        serde_wasm_bindgen::to_value(&analysis::report_bytes(bytes)).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get piece-table fragmentation diagnostics: `{ pieceCount, originalLength,
+    /// addBufferLength, liveAddBytes, garbageAddBytes, averagePieceLength }`
+    /// `garbageAddBytes` is what `compact` would reclaim
+    #[wasm_bindgen(js_name = debugStats)]
+    pub fn debug_stats(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.piece_table.debug_stats()).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get approximate heap usage: `{ originalBytes, addBufferBytes, piecesBytes,
+    /// cacheBytes, historyBytes, totalBytes }`
+    /// Hosts can use it to decide when to `compact`, `clearHistory` or evict a document
+    #[wasm_bindgen(js_name = memoryUsage)]
+    pub fn memory_usage(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.memory_usage_report()).unwrap_or(JsValue::NULL)
+    }
+
+    fn memory_usage_report(&self) -> MemoryUsage {
+        self.piece_table.memory_usage().with_history_bytes(self.history.memory_bytes())
+    }
+
+    /// Check the internal consistency of the document, for use while fuzzing
+    #[wasm_bindgen]
+    pub fn validate(&self) -> bool {
+        self.piece_table.validate()
+    }
+
+    /// Get the text of `length` bytes starting at a byte offset
+    /// Returns None if the range is out of bounds or either end is inside a character
+    #[wasm_bindgen(js_name = getTextRange)]
+    pub fn get_text_range(&self, offset: usize, length: usize) -> Option<String> {
+        if !self.piece_table.is_valid_range(offset, length) {
+            return None;
+        }
+        Some(self.piece_table.get_text_range(offset, length))
+    }
+
+    /// Split the document into chunks of at most `max_chunk_bytes` bytes
+    /// Chunk boundaries never split a multibyte character
+    #[wasm_bindgen(js_name = toChunks)]
+    pub fn to_chunks(&self, max_chunk_bytes: usize) -> JsValue {
+        let chunks = self.piece_table.to_chunks(max_chunk_bytes);
+        serde_wasm_bindgen::to_value(&chunks).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get the number of visual rows a line occupies when soft-wrapped
+    /// Returns 0 if the line doesn't exist
+    #[wasm_bindgen(js_name = wrappedLineHeight)]
+    pub fn wrapped_line_height(&self, line: usize, max_columns: usize, tab_width: usize) -> usize {
+        match self.piece_table.get_line(line) {
+            Some(text) => layout::wrap_points(&text, max_columns, tab_width).len() + 1,
+            None => 0,
+        }
+    }
+
+    /// Get the ranges of the visual rows of a line soft-wrapped at `max_columns`
+    /// Breaks follow `wrappedLineHeight`: after whitespace where possible, inside
+    /// over-long words otherwise. A line that fits is one range; a missing line
+    /// gives an empty array
+    #[wasm_bindgen(js_name = wrapLine)]
+    pub fn wrap_line(&self, line: usize, max_columns: usize, tab_width: usize) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.wrap_line_ranges(line, max_columns, tab_width)).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get the visual column of an offset, with tabs advancing to the next tab stop
+    #[wasm_bindgen(js_name = visualColumn)]
+    pub fn visual_column(&self, offset: usize, tab_width: usize) -> usize {
+        let position = self.piece_table.offset_to_position(offset);
+        let line = self.piece_table.get_line(position.line).unwrap_or_default();
+        layout::visual_column(&line, position.column, tab_width)
+    }
+
+    /// Convert a visual column on a line to a byte offset, expanding tabs
+    /// A column inside a tab maps to the tab itself and columns past the line end
+    /// are clamped; returns None if the line doesn't exist
+    #[wasm_bindgen(js_name = visualColumnToOffset)]
+    pub fn visual_column_to_offset(&self, line: usize, visual_column: usize, tab_width: usize) -> Option<usize> {
+        let text = self.piece_table.get_line(line)?;
+        let line_offset = self.piece_table.get_line_offset(line)?;
+        Some(line_offset + layout::column_for_visual(&text, visual_column, tab_width))
+    }
+
+    /// Move a column on a line by a number of visual columns, expanding tabs
+    /// Returns the resulting position, clamped to the line, or null if the line doesn't exist
+    #[wasm_bindgen(js_name = moveByVisualColumn)]
+    pub fn move_by_visual_column(&self, line: usize, column: usize, delta_visual: isize, tab_width: usize) -> JsValue {
+        match self.piece_table.get_line(line) {
+            Some(text) => {
+                let column = layout::move_by_visual_column(&text, column, delta_visual, tab_width);
+                serde_wasm_bindgen::to_value(&Position::new(line, column)).unwrap_or(JsValue::NULL)
+            }
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Convert a byte offset to a position (line, byte column)
+    #[wasm_bindgen(js_name = offsetToPosition)]
+    pub fn offset_to_position(&self, offset: usize) -> JsValue {
+        let position = self.piece_table.offset_to_position(offset);
+        serde_wasm_bindgen::to_value(&position).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get the line containing an offset without computing the column
+    #[wasm_bindgen(js_name = lineAtOffset)]
+    pub fn line_at_offset(&self, offset: usize) -> usize {
+        self.piece_table.line_at_offset(offset)
+    }
+
+    /// Convert a position (line, byte column) to a byte offset
+    #[wasm_bindgen(js_name = positionToOffset)]
+    pub fn position_to_offset(&self, line: usize, column: usize) -> Option<usize> {
+        self.piece_table.position_to_offset(line, column)
+    }
+
+    /// Convert an array of byte offsets to positions in one ordered pass
+    /// Results are in input order and match `offsetToPosition` for each offset;
+    /// returns null if the input is not an array of non-negative integers
+    #[wasm_bindgen(js_name = offsetsToPositions)]
+    pub fn offsets_to_positions(&self, offsets: JsValue) -> JsValue {
+        match self.decode_offsets_to_positions(serde_wasm_bindgen::Deserializer::from(offsets)) {
+            Ok(positions) => serde_wasm_bindgen::to_value(&positions).unwrap_or(JsValue::NULL),
+            Err(_) => JsValue::NULL,
+        }
+    }
+
+    /// Convert an array of `{ line, column }` positions to byte offsets in one ordered pass
+    /// Results are in input order, with null for positions on missing lines;
+    /// returns null if the input is not an array of positions
+    #[wasm_bindgen(js_name = positionsToOffsets)]
+    pub fn positions_to_offsets(&self, positions: JsValue) -> JsValue {
+        match self.decode_positions_to_offsets(serde_wasm_bindgen::Deserializer::from(positions)) {
+            Ok(offsets) => serde_wasm_bindgen::to_value(&offsets).unwrap_or(JsValue::NULL),
+            Err(_) => JsValue::NULL,
+        }
+    }
+
+    /// Insert text at a position (line, byte column)
+    /// Positions past the end of a line or document are clamped to it
+    #[wasm_bindgen(js_name = insertAt)]
+    pub fn insert_at(&mut self, line: usize, column: usize, text: &str) -> bool {
+        let offset = self.clamped_position_offset(Position::new(line, column));
+        self.insert(offset, text)
+    }
+
+    /// Delete the text in a range of positions as a single operation
+    /// The range may span lines; positions are clamped and may be given in either order
+    #[wasm_bindgen(js_name = deleteRange)]
+    pub fn delete_range(&mut self, range: JsValue) -> bool {
+        match serde_wasm_bindgen::from_value::<Range>(range) {
+            Ok(range) => self.delete_position_range(range),
+            Err(_) => false,
+        }
+    }
+
+    /// Get the text in a range of positions, clamped like `deleteRange`
+    #[wasm_bindgen(js_name = getTextInRange)]
+    pub fn get_text_in_range(&self, range: JsValue) -> Option<String> {
+        let range = serde_wasm_bindgen::from_value::<Range>(range).ok()?;
+        let (start, end) = self.position_range_offsets(range);
+        Some(self.piece_table.get_text_range(start, end - start))
+    }
+
+    /// Get text in a specific range, clamped to the document bounds
+    /// Returns the part of the range that overlaps the document instead of None;
+    /// ends inside a character are snapped back to its start
+    #[wasm_bindgen(js_name = getTextRangeClamped)]
+    pub fn get_text_range_clamped(&self, offset: usize, length: usize) -> String {
+        let start = self.piece_table.floor_char_boundary(offset);
+        let end = self.piece_table.floor_char_boundary(offset.saturating_add(length));
+        self.piece_table.get_text_range(start, end - start)
+    }
+
+    /// Get `length` characters (Unicode scalar values) starting at a character offset
+    /// Never returns null: a range running past the end is clamped to it, and an
+    /// offset at or past the end gives an empty string. Only the slice is copied
+    #[wasm_bindgen(js_name = getTextSlice)]
+    pub fn get_text_slice(&self, offset: usize, length: usize) -> String {
+        self.piece_table.char_slice(offset, length)
+    }
+
+    /// Get byte, character, word and line counts for the whole document in one pass
+    #[wasm_bindgen]
+    pub fn stats(&self) -> JsValue {
+        let stats = DocumentStats::from_chunks(self.piece_table.chunks());
+        serde_wasm_bindgen::to_value(&stats).unwrap_or(JsValue::NULL)
+    }
+
+    /// Get the same counts as `stats` for a range of positions, clamped like `deleteRange`
+    #[wasm_bindgen(js_name = statsInRange)]
+    pub fn stats_in_range(&self, range: JsValue) -> JsValue {
+        match serde_wasm_bindgen::from_value::<Range>(range) {
+            Ok(range) => serde_wasm_bindgen::to_value(&self.range_stats(range)).unwrap_or(JsValue::NULL),
+            Err(_) => JsValue::NULL,
+        }
+    }
+
+    /// Get character, grapheme, word, line and byte counts for a selection
+    /// Offsets are snapped back to char boundaries and may be given in either order
+    #[wasm_bindgen(js_name = selectionStats)]
+    pub fn selection_stats(&self, start_offset: usize, end_offset: usize) -> JsValue {
+        let stats = self.selection_stats_for(start_offset, end_offset);
+        serde_wasm_bindgen::to_value(&stats).unwrap_or(JsValue::NULL)
+    }
+
+    /// Set a callback classifying an offset as "code", "string" or "comment"
+    /// Bracket helpers ignore brackets the classifier doesn't report as code.
+    /// Pass undefined to remove the classifier.
+    #[wasm_bindgen(js_name = setTokenClassifier)]
+    pub fn set_token_classifier(&mut self, classifier: Option<js_sys::Function>) {
+        match classifier {
+            Some(function) => self.set_token_classifier_fn(move |offset| {
+                function
+                    .call1(&JsValue::NULL, &JsValue::from(offset))
+                    .ok()
+                    .and_then(|kind| kind.as_string())
+                    .map_or(TokenKind::Code, |kind| TokenKind::parse(&kind))
+            }),
+            None => self.token_classifier = None,
+        }
+    }
+
+    /// Register a callback invoked after every content change, including undo and redo
+    /// The callback receives `{ opType, offset, removedLength, insertedText, lineDelta }`
+    /// and must not edit the document itself. Returns an id for `offChange`
+    #[wasm_bindgen(js_name = onChange)]
+    pub fn on_change(&mut self, callback: js_sys::Function) -> u32 {
+        self.add_change_listener(move |event| {
+            if let Ok(value) = serde_wasm_bindgen::to_value(event) {
+                let _ = callback.call1(&JsValue::NULL, &value);
+            }
+        })
+    }
+
+    /// Remove a callback registered with `onChange`
+    #[wasm_bindgen(js_name = offChange)]
+    pub fn off_change(&mut self, listener_id: u32) -> bool {
+        let count = self.change_listeners.len();
+        self.change_listeners.retain(|(id, _)| *id != listener_id);
+        self.change_listeners.len() != count
+    }
+
+    /// Get a hex-encoded 64-bit FNV-1a hash of the whole text
+    /// Streamed over pieces, so equal text hashes equally however it was edited
+    #[wasm_bindgen(js_name = contentHash)]
+    pub fn content_hash(&self) -> String {
+        format!("{:016x}", hash::hash_chunks(self.piece_table.chunks()))
+    }
+
+    /// Get a hex-encoded 64-bit FNV-1a hash of the text in a range
+    /// The range is clamped to the document and snapped back to char boundaries
+    #[wasm_bindgen(js_name = rangeHash)]
+    pub fn range_hash(&self, offset: usize, length: usize) -> String {
+        format!("{:016x}", self.range_hash_value(offset, length))
+    }
+
+    /// Get the offset of the bracket matching the one at or just before an offset
+    /// Brackets the token classifier reports as strings or comments are ignored;
+    /// returns None if there is no adjacent bracket or it is unmatched
+    #[wasm_bindgen(js_name = matchBracket)]
+    pub fn match_bracket(&self, offset: usize) -> Option<usize> {
+        brackets::matching_bracket(self.piece_table.text(), offset, |i| self.is_code_at(i))
+    }
+
+    /// Get the range of the innermost bracket pair enclosing an offset
+    /// Returns null if the offset is at top level or the brackets are unbalanced
+    #[wasm_bindgen(js_name = enclosingBracketRange)]
+    pub fn enclosing_bracket_range(&self, offset: usize) -> JsValue {
+        match brackets::enclosing_pair(self.piece_table.text(), offset, |i| self.is_code_at(i)) {
+            Some((open, close)) => {
+                let range = Range::new(
+                    self.piece_table.offset_to_position(open),
+                    self.piece_table.offset_to_position(close + 1),
+                );
+                serde_wasm_bindgen::to_value(&range).unwrap_or(JsValue::NULL)
+            }
+            None => JsValue::NULL,
+        }
+    }
+
+    fn selection_stats_for(&self, start_offset: usize, end_offset: usize) -> SelectionStats {
+        let start = self.piece_table.floor_char_boundary(start_offset.min(end_offset));
+        let end = self.piece_table.floor_char_boundary(start_offset.max(end_offset));
+        SelectionStats::from_text(&self.piece_table.get_text_range(start, end - start))
+    }
+
+    fn apply_text_edits(&mut self, edits: &[TextEdit]) -> Result<bool, EditError> {
+        if self.read_only {
+            return Ok(false);
+        }
+        let order = edits::application_order(edits, |offset, length| self.piece_table.is_valid_range(offset, length))?;
+
+        self.history.begin_transaction();
+        for edit in order.into_iter().map(|index| &edits[index]) {
+            match (edit.length, edit.text.is_empty()) {
+                (0, _) => self.insert(edit.offset, &edit.text),
+                (length, true) => self.delete(edit.offset, length),
+                (length, false) => self.replace(edit.offset, length, &edit.text),
+            };
+        }
+        self.history.end_transaction();
+        Ok(true)
+    }
+
+    fn apply_patch_entries(&mut self, entries: &[PatchEntry]) -> bool {
+        if self.read_only {
+            return false;
+        }
+        // Dry-run on a copy so a bad entry leaves the document untouched
+        let mut preview = self.piece_table.clone();
+        let mut offsets = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            let offset = match entry.unit {
+                OffsetUnit::Byte => Some(entry.offset),
+                OffsetUnit::Char => patch::char_to_byte_offset(preview.chunks(), entry.offset),
+            };
+            let Some(offset) = offset else {
+                return false;
+            };
+
+            let consistent = match entry.op {
+                PatchOp::Insert => entry.removed.is_empty(),
+                PatchOp::Delete => entry.inserted.is_empty(),
+                PatchOp::Replace => true,
+            };
+            let in_bounds = offset
+                .checked_add(entry.removed.len())
+                .is_some_and(|end| end <= preview.get_length())
+                && preview.floor_char_boundary(offset) == offset;
+            if !consistent || !in_bounds || preview.get_text_range(offset, entry.removed.len()) != entry.removed {
+                return false;
+            }
+
+            preview.delete(offset, entry.removed.len());
+            preview.insert(offset, &entry.inserted);
+            offsets.push(offset);
+        }
+
+        self.history.begin_transaction();
+        for (entry, offset) in entries.iter().zip(offsets) {
+            match entry.op {
+                PatchOp::Insert => self.insert(offset, &entry.inserted),
+                PatchOp::Delete => self.delete(offset, entry.removed.len()),
+                PatchOp::Replace => self.replace(offset, entry.removed.len(), &entry.inserted),
+            };
+        }
+        self.history.end_transaction();
+        true
+    }
+
+    fn apply_operations(&mut self, operations: &[Operation]) -> bool {
+        if self.read_only {
+            return false;
+        }
+
+        // Dry-run on a copy so a bad operation leaves the document untouched
+        let mut preview = self.piece_table.clone();
+        for operation in operations {
+            let removed = match operation.op_type {
+                OperationType::Insert => "",
+                OperationType::Delete => operation.text.as_str(),
+                OperationType::Replace => operation.old_text.as_deref().unwrap_or_default(),
+                OperationType::Compound => return false,
+            };
+            let fits = preview.is_valid_range(operation.offset, removed.len());
+            if !fits || preview.get_text_range(operation.offset, removed.len()) != removed {
+                return false;
+            }
+            let inserted = if operation.op_type == OperationType::Delete { "" } else { &operation.text };
+            preview.delete(operation.offset, removed.len());
+            preview.insert(operation.offset, inserted);
+        }
+
+        self.history.begin_transaction();
+        for operation in operations {
+            match operation.op_type {
+                OperationType::Insert => self.insert(operation.offset, &operation.text),
+                OperationType::Delete => self.delete(operation.offset, operation.text.len()),
+                _ => {
+                    let removed = operation.old_text.as_deref().unwrap_or_default();
+                    self.replace(operation.offset, removed.len(), &operation.text)
+                }
+            };
+        }
+        self.history.end_transaction();
+        true
+    }
+
+    fn utf16_position_at(&self, utf16_offset: usize) -> Option<Position> {
+        let offset = self.piece_table.utf16_to_offset(utf16_offset)?;
+        let position = self.piece_table.offset_to_position(offset);
+        let line_offset = offset - position.column;
+        let column = self.piece_table.offset_to_utf16(offset) - self.piece_table.offset_to_utf16(line_offset);
+        Some(Position::new(position.line, column))
+    }
+
+    fn import_history_json(&mut self, json: &str) -> Result<(), String> {
+        let history = History::import(json)?;
+
+        // Replay the stacks on a copy: undo everything, then redo everything
+        let mut preview = self.piece_table.clone();
+        let undo_fits = history
+            .undo_operations()
+            .iter()
+            .rev()
+            .all(|operation| Self::try_splice(&mut preview, operation, true));
+        if !undo_fits {
+            return Err("undo history doesn't match the document".to_string());
+        }
+        let mut preview = self.piece_table.clone();
+        let redo_fits = history
+            .redo_operations()
+            .iter()
+            .rev()
+            .all(|operation| Self::try_splice(&mut preview, operation, false));
+        if !redo_fits {
+            return Err("redo history doesn't match the document".to_string());
+        }
+
+        self.history = history;
+        Ok(())
+    }
+
+    /// Apply an operation, or its inverse, to a piece table if the text it
+    /// expects to remove is there
+    fn try_splice(piece_table: &mut PieceTable, operation: &Operation, inverse: bool) -> bool {
+        if operation.op_type == OperationType::Compound {
+            return if inverse {
+                operation.operations.iter().rev().all(|child| Self::try_splice(piece_table, child, true))
+            } else {
+                operation.operations.iter().all(|child| Self::try_splice(piece_table, child, false))
+            };
+        }
+
+        let old_text = match operation.op_type {
+            OperationType::Delete => operation.text.as_str(),
+            OperationType::Replace => operation.old_text.as_deref().unwrap_or_default(),
+            _ => "",
+        };
+        let new_text = if operation.op_type == OperationType::Delete { "" } else { operation.text.as_str() };
+        let (removed, inserted) = if inverse { (new_text, old_text) } else { (old_text, new_text) };
+
+        let offset = operation.offset;
+        let fits = piece_table.is_valid_range(offset, removed.len());
+        if !fits || piece_table.get_text_range(offset, removed.len()) != removed {
+            return false;
+        }
+        piece_table.delete(offset, removed.len());
+        piece_table.insert(offset, inserted);
+        true
+    }
+
+    fn from_json(json: &str) -> Result<Document, String> {
+        let (piece_table, history) = persistence::from_json(json)?;
+        let mut document = Document::new(None);
+        document.piece_table = piece_table;
+        document.history = history;
+        Ok(document)
+    }
+
+    /// Convert a position to an offset, clamping lines past the end to the document end
+    fn clamped_position_offset(&self, position: Position) -> usize {
+        self.piece_table
+            .position_to_offset(position.line, position.column)
+            .unwrap_or(self.piece_table.get_length())
+    }
+
+    /// Clamped byte offsets of a range's ends, in document order
+    fn position_range_offsets(&self, range: Range) -> (usize, usize) {
+        let start = self.clamped_position_offset(range.start);
+        let end = self.clamped_position_offset(range.end);
+        (start.min(end), start.max(end))
+    }
+
+    fn range_stats(&self, range: Range) -> DocumentStats {
+        let (start, end) = self.position_range_offsets(range);
+        DocumentStats::from_chunks(self.piece_table.chunks_in_range(start, end - start))
+    }
+
+    fn delete_position_range(&mut self, range: Range) -> bool {
+        let (start, end) = self.position_range_offsets(range);
+        self.delete(start, end - start)
+    }
+
+    fn search_options(options: JsValue) -> SearchOptions {
+        if options.is_undefined() || options.is_null() {
+            return SearchOptions::default();
+        }
+        serde_wasm_bindgen::from_value(options).unwrap_or_default()
+    }
+
+    fn find_range(&self, query: &str, start_offset: usize, options: SearchOptions) -> Option<Range> {
+        let start = self.piece_table.floor_char_boundary(start_offset);
+        let (match_start, match_end) = search::find_next(self.piece_table.text(), query, start, options)?;
+        Some(self.byte_range(match_start, match_end))
+    }
+
+    fn wrap_line_ranges(&self, line: usize, max_columns: usize, tab_width: usize) -> Vec<Range> {
+        let Some(text) = self.piece_table.get_line(line) else {
+            return Vec::new();
+        };
+        let points = layout::wrap_points(&text, max_columns, tab_width);
+        let starts = std::iter::once(0).chain(points.iter().copied());
+        let ends = points.iter().copied().chain(std::iter::once(text.len()));
+        starts
+            .zip(ends)
+            .map(|(start, end)| Range::from_positions(line, start, line, end))
+            .collect()
+    }
+
+    fn grapheme_position(&self, offset: usize) -> Position {
+        let offset = self.piece_table.floor_char_boundary(offset);
+        let position = self.piece_table.offset_to_position(offset);
+        let column = grapheme::column(self.piece_table.text(), offset - position.column, offset);
+        Position::new(position.line, column)
+    }
+
+    fn search_cursor_with(&self, query: &str, options: SearchOptions) -> SearchCursor {
+        SearchCursor::new(self, query, options)
+    }
+
+    fn find_prev_range(&self, query: &str, start_offset: usize, options: SearchOptions) -> Option<Range> {
+        let end = self.piece_table.floor_char_boundary(start_offset);
+        let (match_start, match_end) = search::find_prev(self.piece_table.text(), query, end, options)?;
+        Some(self.byte_range(match_start, match_end))
+    }
+
+    fn find_all_ranges(&self, query: &str, options: SearchOptions) -> Vec<Range> {
+        search::find_all(self.piece_table.text(), query, options)
+            .into_iter()
+            .map(|(start, end)| self.byte_range(start, end))
+            .collect()
+    }
+
+    fn regex_match_ranges(&self, pattern: &str, flags: &str) -> Result<Vec<RegexMatchRange>, RegexError> {
+        let regex = regex_search::compile(pattern, flags)?;
+        Ok(regex_search::find_all(&regex, self.piece_table.text())
+            .into_iter()
+            .map(|found| RegexMatchRange {
+                range: self.byte_range(found.start, found.end),
+                groups: found
+                    .groups
+                    .iter()
+                    .map(|group| group.map(|(start, end)| self.byte_range(start, end)))
+                    .collect(),
+            })
+            .collect())
+    }
+
+    fn replace_all_regex_matches(&mut self, pattern: &str, replacement: &str, flags: &str) -> Result<usize, RegexError> {
+        let regex = regex_search::compile(pattern, flags)?;
+        if self.read_only {
+            return Ok(0);
+        }
+        let text = self.piece_table.get_text();
+        let replacements: Vec<(usize, usize, String)> = regex
+            .captures_iter(&text)
+            .map(|captures| {
+                let whole = captures.get(0).expect("group 0 always participates");
+                let mut expanded = String::new();
+                captures.expand(replacement, &mut expanded);
+                (whole.start(), whole.end(), expanded)
+            })
+            .collect();
+
+        // Apply from the end so earlier offsets stay valid
+        self.history.begin_transaction();
+        for (start, end, expanded) in replacements.iter().rev() {
+            self.replace(*start, end - start, expanded);
+        }
+        self.history.end_transaction();
+        Ok(replacements.len())
+    }
+
+    /// Replace every match inside one transaction, so it undoes as a single step
+    /// Returns the ranges of the replacements after the edit
+    fn replace_all_matches(&mut self, query: &str, replacement: &str, options: SearchOptions) -> Vec<Range> {
+        if self.read_only {
+            return Vec::new();
+        }
+        let matches = search::find_all(self.piece_table.text(), query, options);
+        if matches.is_empty() {
+            return Vec::new();
+        }
+
+        // Apply from the end so earlier offsets stay valid
+        self.history.begin_transaction();
+        for &(start, end) in matches.iter().rev() {
+            self.replace(start, end - start, replacement);
+        }
+        self.history.end_transaction();
+
+        let mut delta = 0isize;
+        let mut replaced_offsets = Vec::with_capacity(matches.len());
+        for &(start, end) in &matches {
+            replaced_offsets.push(start.saturating_add_signed(delta));
+            delta += replacement.len() as isize - (end - start) as isize;
+        }
+        replaced_offsets
+            .into_iter()
+            .map(|start| self.byte_range(start, start + replacement.len()))
+            .collect()
+    }
+
+    fn word_range(&self, offset: usize) -> Range {
+        let offset = self.piece_table.floor_char_boundary(offset);
+        let (start, end) = word::word_range_at(self.piece_table.text(), offset);
+        self.byte_range(start, end)
+    }
+
+    fn byte_range(&self, start: usize, end: usize) -> Range {
+        Range::new(
+            self.piece_table.offset_to_position(start),
+            self.piece_table.offset_to_position(end),
+        )
+    }
+
+    fn range_hash_value(&self, offset: usize, length: usize) -> u64 {
+        let start = self.piece_table.floor_char_boundary(offset);
+        let end = self.piece_table.floor_char_boundary(offset.saturating_add(length));
+        hash::hash_chunks(self.piece_table.chunks_in_range(start, end - start))
+    }
+
+    fn set_token_classifier_fn(&mut self, classifier: impl Fn(usize) -> TokenKind + 'static) {
+        self.token_classifier = Some(Box::new(classifier));
+    }
+
+    /// Whether an offset holds code according to the token classifier, if any
+    fn is_code_at(&self, offset: usize) -> bool {
+        self.token_classifier
+            .as_ref()
+            .is_none_or(|classify| classify(offset) == TokenKind::Code)
+    }
+
+    /// Replace the content with `new_text` as a single operation covering only
+    /// the region between the common prefix and suffix
+    fn replace_changed_region(&mut self, new_text: &str) -> bool {
+        let text = self.piece_table.get_text();
+        let prefix = text
+            .chars()
+            .zip(new_text.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum::<usize>();
+        let suffix = text[prefix..]
+            .chars()
+            .rev()
+            .zip(new_text[prefix..].chars().rev())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum::<usize>();
+
+        if prefix == text.len() && prefix == new_text.len() {
+            return false;
+        }
+        self.replace(prefix, text.len() - prefix - suffix, &new_text[prefix..new_text.len() - suffix])
+    }
+
+    fn is_blank_line(&self, line: usize) -> bool {
+        self.piece_table.get_line(line).is_none_or(|text| text.trim().is_empty())
+    }
+
+    /// Length of a line without its carriage return, and how many spaces and tabs end it
+    fn trailing_whitespace(line: &str) -> (usize, usize) {
+        let body = line.strip_suffix('\r').unwrap_or(line);
+        let trimmed = body.trim_end_matches([' ', '\t']);
+        (body.len(), body.len() - trimmed.len())
+    }
+
+    /// Number of leading whitespace characters, or None for a blank line
+    fn indent_width(line: &str) -> Option<usize> {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            return None;
+        }
+        Some(line[..line.len() - trimmed.len()].chars().count())
+    }
+
+    fn decode_offsets_to_positions<'de, D: serde::Deserializer<'de>>(
+        &self,
+        offsets: D,
+    ) -> Result<Vec<Position>, D::Error> {
+        let offsets = Vec::<usize>::deserialize(offsets)?;
+        Ok(self.piece_table.offsets_to_positions(&offsets))
+    }
+
+    fn decode_positions_to_offsets<'de, D: serde::Deserializer<'de>>(
+        &self,
+        positions: D,
+    ) -> Result<Vec<Option<usize>>, D::Error> {
+        let positions = Vec::<Position>::deserialize(positions)?;
+        Ok(self.piece_table.positions_to_offsets(&positions))
+    }
+
+    fn append_with_range(&mut self, text: &str) -> Option<Range> {
+        let end_offset = self.piece_table.get_length();
+        let start = self.piece_table.offset_to_position(end_offset);
+        if !self.insert(end_offset, text) {
+            return None;
+        }
+        let end = self.piece_table.offset_to_position(self.piece_table.get_length());
+        Some(Range::new(start, end))
+    }
+
+    /// Replace `length` bytes at `offset` with `text` in the piece table
+    /// Every content change goes through here so markers stay in sync
+    fn splice(&mut self, offset: usize, length: usize, text: &str) {
+        let old_lines = self.piece_table.get_line_count();
+        self.piece_table.delete(offset, length);
+        self.piece_table.insert(offset, text);
+        self.after_change(offset, length, text, old_lines);
+    }
+
+    /// Shift markers and notify listeners after `length` bytes at `offset` became `text`
+    fn after_change(&mut self, offset: usize, length: usize, text: &str, old_lines: usize) {
+        for marker in self.markers.values_mut() {
+            marker.adjust(offset, length, text.len());
+        }
+
+        let changed = length > 0 || !text.is_empty();
+        if changed && !self.change_listeners.is_empty() {
+            let line_delta = self.piece_table.get_line_count() as isize - old_lines as isize;
+            let event = ChangeEvent::new(offset, length, text, line_delta);
+            for (_, listener) in &self.change_listeners {
+                listener(&event);
+            }
+        }
+    }
+
+    fn add_change_listener(&mut self, listener: impl Fn(&ChangeEvent) + 'static) -> u32 {
+        let id = self.next_listener_id;
+        self.next_listener_id += 1;
+        self.change_listeners.push((id, Box::new(listener)));
+        id
+    }
+
+    fn apply_operation(&mut self, operation: &Operation) {
+        match operation.op_type {
+            OperationType::Insert | OperationType::Replace => {
+                self.splice(operation.offset, operation.removed_length(), &operation.text);
+            }
+            OperationType::Delete => {
+                self.splice(operation.offset, operation.removed_length(), "");
+            }
+            OperationType::Compound => {
+                for child in &operation.operations {
+                    self.apply_operation(child);
+                }
+            }
+        }
+    }
+
+    fn apply_inverse_operation(&mut self, operation: &Operation) {
+        match operation.op_type {
+            OperationType::Insert => {
+                self.splice(operation.offset, operation.inserted_length(), "");
+            }
+            OperationType::Delete => {
+                self.splice(operation.offset, 0, &operation.text);
+            }
+            OperationType::Replace => {
+                let old_text = operation.old_text.as_deref().unwrap_or_default();
+                self.splice(operation.offset, operation.inserted_length(), old_text);
+            }
+            OperationType::Compound => {
+                for child in operation.operations.iter().rev() {
+                    self.apply_inverse_operation(child);
+                }
+            }
+        }
+    }
+}
+
+/// Initialize the WASM module
+#[wasm_bindgen(start)]
+pub fn init() {
+    // WASM module initialization
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_text_range_clamped() {
+        let doc = Document::new(Some("hello world".to_string()));
+        assert_eq!(doc.get_text_range(6, 10), None);
+        assert_eq!(doc.get_text_range_clamped(6, 10), "world");
+        assert_eq!(doc.get_text_range_clamped(20, 5), "");
+        assert_eq!(doc.get_text_range_clamped(0, usize::MAX), "hello world");
+    }
+
+    #[test]
+    fn test_wrapped_line_height() {
+        let doc = Document::new(Some("short\nthe quick brown fox jumps\n".to_string()));
+        assert_eq!(doc.wrapped_line_height(0, 10, 4), 1);
+        assert_eq!(doc.wrapped_line_height(1, 10, 4), 3);
+        assert_eq!(doc.wrapped_line_height(5, 10, 4), 0);
+    }
+
+    #[test]
+    fn test_append_with_range() {
+        let mut doc = Document::new(Some("one\ntwo".to_string()));
+        let range = doc.append_with_range("\nthree\nfo");
+        assert_eq!(range, Some(Range::from_positions(1, 3, 3, 2)));
+        assert_eq!(doc.get_text(), "one\ntwo\nthree\nfo");
+
+        doc.undo();
+        assert_eq!(doc.get_text(), "one\ntwo");
+
+        doc.set_read_only(true);
+        assert_eq!(doc.append_with_range("!"), None);
+        assert_eq!(doc.get_text(), "one\ntwo");
+        doc.set_read_only(false);
+        assert_eq!(doc.append_with_range(""), Some(Range::from_positions(1, 3, 1, 3)));
+    }
+
+    #[test]
+    fn test_replace_all_whole_word() {
+        let mut doc = Document::new(Some("let id = width(id);\nid.x = ID;".to_string()));
+        assert_eq!(doc.replace_all_whole_word("id", "key", true), 3);
+        assert_eq!(doc.get_text(), "let key = width(key);\nkey.x = ID;");
+
+        doc.undo();
+        assert_eq!(doc.get_text(), "let id = width(id);\nid.x = ID;");
+        assert!(!doc.can_undo());
+
+        assert_eq!(doc.replace_all_whole_word("id", "key", false), 4);
+        assert_eq!(doc.replace_all_whole_word("missing", "x", true), 0);
+    }
+
+    #[test]
+    fn test_common_prefix_and_suffix() {
+        let mut a = Document::new(Some("caf\u{e9} header\nbody\nfooter".to_string()));
+        a.insert(13, "old ");
+        let b = Document::new(Some("caf\u{e9} header\nnew body\nfooter".to_string()));
+
+        assert_eq!(a.common_prefix_len(&b), 13);
+        assert_eq!(a.common_suffix_len(&b), 12);
+        assert_eq!(a.common_prefix_len(&a), a.get_length());
+
+        let c = Document::new(Some("caf\u{e8}".to_string()));
+        assert_eq!(a.common_prefix_len(&c), 3);
+    }
+
+    #[test]
+    fn test_selection_stats_snaps_offsets() {
+        let doc = Document::new(Some("x caf\u{e9}\nd\u{e9}j\u{e0} vu".to_string()));
+        let stats = doc.selection_stats_for(14, 3);
+        assert_eq!(stats, SelectionStats::from_text("af\u{e9}\nd\u{e9}j\u{e0}"));
+        assert_eq!(stats.lines, 2);
+        assert_eq!(stats.words, 2);
+    }
+
+    #[test]
+    fn test_retype_after_undo_keeps_redo_chain() {
+        let mut doc = Document::new(None);
+        doc.set_preserve_matching_redo(true);
+        doc.insert(0, "a");
+        doc.insert(1, "b");
+        doc.insert(2, "c");
+        doc.undo();
+        doc.undo();
+
+        doc.insert(1, "b");
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "abc");
+    }
+
+    #[test]
+    fn test_json_patch_round_trip() {
+        let initial = "caf\u{e9}\nline two\n";
+        let mut source = Document::new(Some(initial.to_string()));
+        source.insert(5, "au lait ");
+        source.delete(0, 1);
+        source.replace(12, 4, "2");
+        source.insert(source.get_length(), "\u{1f600}");
+
+        let entries = patch::from_operations(source.history.undo_operations());
+        assert_eq!(entries.len(), 4);
+
+        let mut target = Document::new(Some(initial.to_string()));
+        assert!(target.apply_patch_entries(&entries));
+        assert_eq!(target.get_text(), source.get_text());
+        assert_eq!(patch::from_operations(target.history.undo_operations()), entries);
+
+        assert_eq!(target.undo_depth(), 1);
+        assert!(target.undo());
+        assert_eq!(target.get_text(), initial);
+    }
+
+    #[test]
+    fn test_json_patch_char_offsets_across_pieces() {
+        let mut doc = Document::new(Some("\u{e9}t\u{e9}".to_string()));
+        doc.insert(2, "\u{1f600}");
+        let entries = vec![PatchEntry {
+            op: PatchOp::Replace,
+            offset: 2,
+            unit: OffsetUnit::Char,
+            removed: "t".to_string(),
+            inserted: "T".to_string(),
+        }];
+        assert!(doc.apply_patch_entries(&entries));
+        assert_eq!(doc.get_text(), "\u{e9}\u{1f600}T\u{e9}");
+    }
+
+    #[test]
+    fn test_json_patch_rejects_mismatch() {
+        let mut doc = Document::new(Some("hello".to_string()));
+        let entries = vec![
+            PatchEntry {
+                op: PatchOp::Insert,
+                offset: 5,
+                unit: OffsetUnit::Byte,
+                removed: String::new(),
+                inserted: "!".to_string(),
+            },
+            PatchEntry {
+                op: PatchOp::Delete,
+                offset: 0,
+                unit: OffsetUnit::Char,
+                removed: "x".to_string(),
+                inserted: String::new(),
+            },
+        ];
+        assert!(!doc.apply_patch_entries(&entries));
+        assert_eq!(doc.get_text(), "hello");
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_parent_line() {
+        let doc = Document::new(Some(
+            "mod a {\n    fn b() {\n\n        if c {\n            d();\n        }\n    }\n}".to_string(),
+        ));
+        assert_eq!(doc.parent_line(4), Some(3));
+        assert_eq!(doc.parent_line(3), Some(1));
+        assert_eq!(doc.parent_line(1), Some(0));
+        assert_eq!(doc.parent_line(0), None);
+        assert_eq!(doc.parent_line(2), None);
+        assert_eq!(doc.parent_line(99), None);
+    }
+
+    #[test]
+    fn test_reload_identical_content_is_noop() {
+        let mut doc = Document::new(Some("saved\n".to_string()));
+        doc.insert(5, "!");
+        doc.delete(5, 1);
+        let version = doc.piece_table.version();
+
+        assert!(!doc.reload("saved\n", true));
+        assert_eq!(doc.piece_table.version(), version);
+        assert_eq!(doc.history.undo_count(), 2);
+    }
+
+    #[test]
+    fn test_reload_changed_content() {
+        let mut doc = Document::new(Some("old".to_string()));
+        assert!(doc.reload("new\ncontent", false));
+        assert_eq!(doc.get_text(), "new\ncontent");
+        doc.undo();
+        assert_eq!(doc.get_text(), "old");
+
+        assert!(doc.reload("fresh", true));
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_utf16_range_byte_length() {
+        let doc = Document::new(Some("x \u{1f600} caf\u{e9}".to_string()));
+        assert_eq!(doc.utf16_range_byte_length(1, 5), Some(6));
+        assert_eq!(doc.utf16_range_byte_length(0, 9), Some(12));
+        assert_eq!(doc.utf16_range_byte_length(3, 5), None);
+        assert_eq!(doc.utf16_range_byte_length(5, 1), None);
+        assert_eq!(doc.utf16_range_byte_length(0, 10), None);
+    }
+
+    #[test]
+    fn test_reindent_two_to_four_spaces() {
+        let mut doc = Document::new(Some("fn a() {\n  if b {\n    c();\n   }\n\n  d();\n}\n".to_string()));
+        assert_eq!(doc.reindent_to_unit(4), 4);
+        assert_eq!(doc.get_text(), "fn a() {\n    if b {\n        c();\n        }\n\n    d();\n}\n");
+        assert_eq!(doc.history.undo_count(), 1);
+
+        doc.undo();
+        assert_eq!(doc.get_text(), "fn a() {\n  if b {\n    c();\n   }\n\n  d();\n}\n");
+        assert_eq!(doc.reindent_to_unit(2), 1);
+    }
+
+    #[test]
+    fn test_replace_changed_region() {
+        let mut doc = Document::new(Some("abcabc".to_string()));
+        assert!(doc.replace_changed_region("abcXabc"));
+        assert_eq!(doc.get_text(), "abcXabc");
+        let operation = doc.history.undo_operations()[0].clone();
+        assert_eq!((operation.offset, operation.length, operation.text.as_str()), (3, 0, "X"));
+        assert!(!doc.replace_changed_region("abcXabc"));
+
+        assert!(doc.replace_changed_region("ab"));
+        assert_eq!(doc.get_text(), "ab");
+    }
+
+    #[test]
+    fn test_capture_and_restore_state() {
+        let mut doc = Document::new(Some("line one\n".to_string()));
+        let first = doc.capture_state();
+        doc.insert(9, "line two\n");
+        let second = doc.capture_state();
+        doc.delete(0, 5);
+        doc.insert(0, "LINE ");
+
+        assert!(doc.restore_state(first));
+        assert_eq!(doc.get_text(), "line one\n");
+        assert!(doc.restore_state(second));
+        assert_eq!(doc.get_text(), "line one\nline two\n");
+
+        doc.undo();
+        assert_eq!(doc.get_text(), "line one\n");
+        doc.undo();
+        assert_eq!(doc.get_text(), "LINE one\nline two\n");
+
+        assert!(doc.release_state(first));
+        assert!(!doc.restore_state(first));
+    }
+
+    #[test]
+    fn test_non_blank_line_navigation() {
+        let doc = Document::new(Some("first\n\n   \n\t\nsecond\n".to_string()));
+        assert_eq!(doc.next_non_blank_line(0), Some(4));
+        assert_eq!(doc.prev_non_blank_line(4), Some(0));
+        assert_eq!(doc.prev_non_blank_line(2), Some(0));
+        assert_eq!(doc.next_non_blank_line(4), None);
+        assert_eq!(doc.prev_non_blank_line(0), None);
+        assert_eq!(doc.prev_non_blank_line(99), Some(4));
+    }
+
+    #[test]
+    fn test_merge_keystroke_with_auto_closed_bracket() {
+        let mut doc = Document::new(Some("f".to_string()));
+        assert!(!doc.merge_last_two_edits());
+        doc.insert(1, "(");
+        doc.insert(2, ")");
+        assert!(doc.merge_last_two_edits());
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "f");
+        assert!(!doc.can_undo());
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "f()");
+    }
+
+    #[test]
+    fn test_compare_and_replace() {
+        let mut doc = Document::new(Some("let x = 1;".to_string()));
+        assert!(!doc.compare_and_replace(4, 1, "y", "z"));
+        assert!(!doc.compare_and_replace(8, 5, "1;", "2;"));
+        assert_eq!(doc.get_text(), "let x = 1;");
+        assert!(!doc.can_undo());
+
+        assert!(doc.compare_and_replace(4, 1, "x", "count"));
+        assert_eq!(doc.get_text(), "let count = 1;");
+    }
+
+    #[test]
+    fn test_trim_line_trailing() {
+        let mut doc = Document::new(Some("keep  \nfix \t \r\nlast".to_string()));
+        assert!(doc.trim_line_trailing(1));
+        assert_eq!(doc.get_text(), "keep  \nfix\r\nlast");
+        assert!(!doc.trim_line_trailing(1));
+        assert!(!doc.trim_line_trailing(2));
+        assert!(!doc.trim_line_trailing(9));
+
+        doc.undo();
+        assert_eq!(doc.get_text(), "keep  \nfix \t \r\nlast");
+    }
+
+    #[test]
+    fn test_token_classifier_skips_string_brackets() {
+        let mut doc = Document::new(Some("{ s = \"}\"; x }".to_string()));
+        let text = doc.get_text();
+        assert_eq!(brackets::enclosing_pair(&text, 12, |i| doc.is_code_at(i)), None);
+
+        doc.set_token_classifier_fn(|offset| {
+            if (6..9).contains(&offset) {
+                TokenKind::String
+            } else {
+                TokenKind::Code
+            }
+        });
+        assert_eq!(brackets::enclosing_pair(&text, 12, |i| doc.is_code_at(i)), Some((0, 13)));
+    }
+
+    #[test]
+    fn test_range_hash_tracks_range_content() {
+        let mut doc = Document::new(Some("header\nviewport text\nfooter".to_string()));
+        let before = doc.range_hash(7, 13);
+        assert_eq!(before.len(), 16);
+
+        doc.insert(doc.get_length(), " more");
+        assert_eq!(doc.range_hash(7, 13), before);
+
+        doc.insert(15, "!");
+        assert_ne!(doc.range_hash(7, 13), before);
+        assert_eq!(doc.range_hash_value(7, 13), hash::hash_chunks(["viewport! tex"]));
+    }
+
+    #[test]
+    fn test_undo_redo_replace_with_multibyte_text() {
+        let mut doc = Document::new(Some("say h\u{e9}llo there".to_string()));
+        assert!(doc.replace(4, 6, "\u{1f44b}\u{1f44b}!"));
+        assert_eq!(doc.get_text(), "say \u{1f44b}\u{1f44b}! there");
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "say h\u{e9}llo there");
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "say \u{1f44b}\u{1f44b}! there");
+
+        assert!(doc.replace(4, 9, "x"));
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "say \u{1f44b}\u{1f44b}! there");
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "say h\u{e9}llo there");
+    }
+
+    #[test]
+    fn test_undo_replace_ascii_with_multibyte_restores_text() {
+        let mut doc = Document::new(Some("cafe".to_string()));
+        assert!(doc.replace(0, 4, "caf\u{e9}"));
+        assert_eq!(doc.get_length(), 5);
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "cafe");
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "caf\u{e9}");
+    }
+
+    #[test]
+    fn test_history_depths_and_summaries() {
+        let mut doc = Document::new(Some("hello".to_string()));
+        assert_eq!((doc.undo_depth(), doc.redo_depth()), (0, 0));
+        assert_eq!(doc.history.peek_undo(), None);
+
+        doc.insert(5, " world");
+        doc.delete(0, 1);
+        doc.replace(0, 4, "J\u{e9}llo");
+        doc.undo();
+        assert_eq!((doc.undo_depth(), doc.redo_depth()), (2, 1));
+
+        let summary = |operation: Option<&Operation>| operation.map(OperationSummary::from);
+        assert_eq!(
+            summary(doc.history.peek_undo()),
+            Some(OperationSummary { op_type: OperationType::Delete, offset: 0, length: 1 })
+        );
+        assert_eq!(
+            summary(doc.history.peek_redo()),
+            Some(OperationSummary { op_type: OperationType::Replace, offset: 0, length: 4 })
+        );
+
+        doc.undo();
+        assert_eq!(
+            summary(doc.history.peek_undo()),
+            Some(OperationSummary { op_type: OperationType::Insert, offset: 5, length: 6 })
+        );
+        assert_eq!((doc.undo_depth(), doc.redo_depth()), (1, 2));
+        assert_eq!(doc.get_text(), "hello world");
+    }
+
+    #[test]
+    fn test_clear_is_undoable() {
+        let mut doc = Document::new(Some("one\ntwo".to_string()));
+        doc.insert(7, "\nthree");
+        assert!(doc.clear());
+        assert_eq!(doc.get_text(), "");
+        assert_eq!(doc.get_length(), 0);
+        assert_eq!(doc.get_line_count(), 1);
+        assert!(!doc.clear());
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "one\ntwo\nthree");
+        assert_eq!(doc.get_line_count(), 3);
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "");
+        assert!(doc.validate());
+    }
+
+    #[test]
+    fn test_wrap_line_at_word_boundaries() {
+        let doc = Document::new(Some("title\nthe quick brown fox jumps over the lazy dog".to_string()));
+        let rows: Vec<String> = doc
+            .wrap_line_ranges(1, 20, 4)
+            .iter()
+            .map(|range| doc.get_line(1).unwrap()[range.start.column..range.end.column].to_string())
+            .collect();
+        assert_eq!(rows, vec!["the quick brown fox ", "jumps over the lazy ", "dog"]);
+
+        assert_eq!(doc.wrap_line_ranges(0, 20, 4), vec![Range::from_positions(0, 0, 0, 5)]);
+        assert!(doc.wrap_line_ranges(2, 20, 4).is_empty());
+    }
+
+    #[test]
+    fn test_wrap_line_breaks_long_token_and_expands_tabs() {
+        let doc = Document::new(Some("see abcdefghijklmnopqrstuvwxyz end\n\t\tab cd".to_string()));
+        assert_eq!(
+            doc.wrap_line_ranges(0, 10, 4),
+            vec![
+                Range::from_positions(0, 0, 0, 4),
+                Range::from_positions(0, 4, 0, 14),
+                Range::from_positions(0, 14, 0, 24),
+                Range::from_positions(0, 24, 0, 34),
+            ]
+        );
+        assert_eq!(
+            doc.wrap_line_ranges(1, 10, 4),
+            vec![Range::from_positions(1, 0, 1, 5), Range::from_positions(1, 5, 1, 7)]
+        );
+    }
+
+    #[test]
+    fn test_text_slice_clamps_in_chars() {
+        let mut doc = Document::new(Some("h\u{e9}llo".to_string()));
+        doc.insert(6, " w\u{f6}rld");
+        assert_eq!(doc.get_text_slice(1, 3), "\u{e9}ll");
+        assert_eq!(doc.get_text_slice(4, 4), "o w\u{f6}");
+        assert_eq!(doc.get_text_slice(8, 3), "rld");
+        assert_eq!(doc.get_text_slice(8, 100), "rld");
+        assert_eq!(doc.get_text_slice(11, 1), "");
+        assert_eq!(doc.get_text_slice(50, 5), "");
+        assert_eq!(doc.get_text_slice(0, usize::MAX), doc.get_text());
+    }
+
+    #[test]
+    fn test_replace_all_keeps_markers_between_matches() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut doc = Document::new(Some("foo middle foo end foo".to_string()));
+        let middle = doc.create_marker(4, MarkerBias::Left).unwrap();
+        let end = doc.create_marker(15, MarkerBias::Right).unwrap();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&events);
+        doc.add_change_listener(move |event| recorded.borrow_mut().push(event.removed_length));
+
+        let ranges = doc.replace_all_matches("foo", "x", SearchOptions::default());
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(*events.borrow(), vec![3, 3, 3]);
+        assert_eq!(doc.get_text(), "x middle x end x");
+        assert_eq!(doc.get_marker_offset(middle), Some(2));
+        assert_eq!(doc.get_marker_offset(end), Some(11));
+        assert_eq!(doc.text_between_markers(middle, end).as_deref(), Some("middle x "));
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "foo middle foo end foo");
+        assert_eq!(doc.get_marker_offset(middle), Some(4));
+    }
+
+    #[test]
+    fn test_replace_all_returns_final_ranges() {
+        let mut doc = Document::new(Some("ab x ab\nab".to_string()));
+        let ranges = doc.replace_all_matches("ab", "hello", SearchOptions::default());
+        assert_eq!(doc.get_text(), "hello x hello\nhello");
+        assert_eq!(
+            ranges,
+            vec![
+                Range::from_positions(0, 0, 0, 5),
+                Range::from_positions(0, 8, 0, 13),
+                Range::from_positions(1, 0, 1, 5),
+            ]
+        );
+        for range in &ranges {
+            let start = doc.piece_table.position_to_offset(range.start.line, range.start.column).unwrap();
+            assert_eq!(doc.piece_table.get_text_range(start, 5), "hello");
+        }
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "ab x ab\nab");
+        assert!(doc.replace_all_matches("zz", "y", SearchOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_find_prev_across_pieces() {
+        let mut doc = Document::new(Some("needle\nhay nee".to_string()));
+        doc.insert(14, "dle hay\nNEEDLE");
+        assert!(doc.piece_table.piece_count() > 1);
+        let options = SearchOptions::default();
+        let range = |start_line, start_column, end_line, end_column| {
+            Some(Range::from_positions(start_line, start_column, end_line, end_column))
+        };
+
+        assert_eq!(doc.find_prev_range("needle", 28, options), range(2, 0, 2, 6));
+        assert_eq!(doc.find_prev_range("needle", 27, options), range(1, 4, 1, 10));
+        assert_eq!(doc.find_prev_range("needle", 17, options), range(1, 4, 1, 10));
+        assert_eq!(doc.find_prev_range("needle", 16, options), range(0, 0, 0, 6));
+        assert_eq!(doc.find_prev_range("needle", 5, options), None);
+
+        let exact = SearchOptions { case_sensitive: true, whole_word: false };
+        assert_eq!(doc.find_prev_range("needle", 28, exact), range(1, 4, 1, 10));
+    }
+
+    #[test]
+    fn test_export_import_history_round_trip() {
+        let mut doc = Document::new(Some("base".to_string()));
+        doc.insert(4, " one");
+        doc.replace(0, 4, "B\u{e4}se");
+        doc.delete(5, 4);
+        doc.undo();
+        let states = ["base", "base one", "B\u{e4}se one"];
+        let exported = doc.export_history();
+
+        doc.clear_history();
+        assert!(!doc.can_undo() && !doc.can_redo());
+        assert_eq!(doc.import_history_json(&exported), Ok(()));
+
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "B\u{e4}se");
+        assert!(doc.undo());
+        for state in states.iter().rev() {
+            assert_eq!(doc.get_text(), *state);
+            doc.undo();
+        }
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_import_history_rejects_mismatched_document() {
+        let mut doc = Document::new(Some("base".to_string()));
+        doc.insert(4, " one");
+        let exported = doc.export_history();
+
+        let mut other = Document::new(Some("different".to_string()));
+        assert!(other.import_history_json(&exported).is_err());
+        assert!(!other.can_undo());
+
+        let mut shorter = Document::new(Some("ba".to_string()));
+        assert!(shorter.import_history_json(&exported).is_err());
+        assert!(other.import_history_json("{\"version\": 99}").is_err());
+        assert!(other.import_history_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_memory_usage_grows_with_edits_and_fragmentation() {
+        let mut doc = Document::new(Some("first line\nsecond line\n".to_string()));
+        let initial = doc.memory_usage_report();
+        assert_eq!(initial.add_buffer_bytes, 0);
+        assert!(initial.original_bytes >= 23);
+
+        for i in 0..8 {
+            doc.insert(i * 2, "x");
+        }
+        let fragmented = doc.memory_usage_report();
+        assert!(fragmented.add_buffer_bytes >= 8);
+        assert!(fragmented.pieces_bytes > initial.pieces_bytes);
+        assert!(fragmented.history_bytes > initial.history_bytes);
+        assert_eq!(
+            fragmented.total_bytes,
+            fragmented.original_bytes
+                + fragmented.add_buffer_bytes
+                + fragmented.pieces_bytes
+                + fragmented.cache_bytes
+                + fragmented.history_bytes
+        );
+    }
+
+    #[test]
+    fn test_debug_stats_track_typing_and_compaction() {
+        let mut doc = Document::new(Some("head\ntail".to_string()));
+        for (i, c) in "typed text".chars().enumerate() {
+            doc.insert(5 + i, &c.to_string());
+        }
+        let stats = doc.piece_table.debug_stats();
+        assert_eq!(stats.piece_count, 3);
+        assert_eq!(stats.original_length, 9);
+        assert_eq!(stats.add_buffer_length, 10);
+        assert_eq!(stats.garbage_add_bytes, 0);
+        assert_eq!(stats.average_piece_length, 19.0 / 3.0);
+
+        doc.delete(5, 6);
+        let stats = doc.piece_table.debug_stats();
+        assert_eq!(stats.live_add_bytes, 4);
+        assert_eq!(stats.garbage_add_bytes, 6);
+
+        assert_eq!(doc.compact(), 6);
+        let stats = doc.piece_table.debug_stats();
+        assert_eq!(stats.add_buffer_length, 4);
+        assert_eq!(stats.garbage_add_bytes, 0);
+        assert_eq!(stats.piece_count, 3);
+    }
+
+    #[test]
+    fn test_arrow_keys_step_over_grapheme_clusters() {
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let doc = Document::new(Some(format!("top\nce\u{301}{}!", family)));
+
+        let mut offset = 4;
+        let mut stops = vec![offset];
+        while offset < doc.get_length() {
+            offset = doc.next_grapheme_boundary(offset);
+            stops.push(offset);
+        }
+        assert_eq!(stops, vec![4, 5, 8, 26, 27]);
+
+        let mut back = vec![offset];
+        while offset > 4 {
+            offset = doc.prev_grapheme_boundary(offset);
+            back.push(offset);
+        }
+        back.reverse();
+        assert_eq!(back, stops);
+
+        assert_eq!(doc.next_grapheme_boundary(12), 26);
+        assert_eq!(doc.prev_grapheme_boundary(12), 8);
+        assert_eq!(doc.grapheme_position(26), Position::new(1, 3));
+        assert_eq!(doc.grapheme_position(12), Position::new(1, 2));
+        assert_eq!(doc.grapheme_position(27), Position::new(1, 4));
+    }
+
+    #[test]
+    fn test_apply_edits_in_any_order() {
+        let text = "let foo = foo + bar(foo);";
+        let edits = [
+            TextEdit::new(20, 3, "value"),
+            TextEdit::new(4, 3, "value"),
+            TextEdit::new(10, 3, "value"),
+        ];
+        let mut doc = Document::new(Some(text.to_string()));
+        assert_eq!(doc.apply_text_edits(&edits), Ok(true));
+
+        let mut manual = Document::new(Some(text.to_string()));
+        manual.replace(20, 3, "value");
+        manual.replace(10, 3, "value");
+        manual.replace(4, 3, "value");
+        assert_eq!(doc.get_text(), manual.get_text());
+        assert_eq!(doc.get_text(), "let value = value + bar(value);");
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), text);
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_apply_edits_rejects_overlap() {
+        let mut doc = Document::new(Some("abcdef".to_string()));
+        let edits = [TextEdit::new(0, 3, "x"), TextEdit::new(4, 1, ""), TextEdit::new(2, 2, "y")];
+        let error = doc.apply_text_edits(&edits).unwrap_err();
+        assert_eq!(error.kind, EditErrorKind::Overlap);
+        assert_eq!(error.index, Some(2));
+        assert_eq!(doc.get_text(), "abcdef");
+        assert!(!doc.can_undo());
+
+        let error = doc.apply_text_edits(&[TextEdit::new(5, 2, "")]).unwrap_err();
+        assert_eq!(error.kind, EditErrorKind::InvalidRange);
+    }
+
+    #[test]
+    fn test_revert_to_checkpoints() {
+        let mut doc = Document::new(None);
+        doc.insert(0, "one");
+        doc.checkpoint("first");
+        doc.insert(3, " two");
+        doc.checkpoint("second");
+        doc.insert(7, " three");
+        doc.delete(0, 4);
+
+        assert!(doc.revert_to_checkpoint("second"));
+        assert_eq!(doc.get_text(), "one two");
+        assert!(doc.revert_to_checkpoint("first"));
+        assert_eq!(doc.get_text(), "one");
+        assert!(doc.revert_to_checkpoint("second"));
+        assert_eq!(doc.get_text(), "one two");
+        assert!(!doc.revert_to_checkpoint("missing"));
+
+        doc.revert_to_checkpoint("first");
+        doc.insert(3, "!");
+        assert!(doc.history.get_checkpoint("second").unwrap().stale);
+        assert!(!doc.revert_to_checkpoint("second"));
+        assert_eq!(doc.get_text(), "one!");
+        assert!(doc.revert_to_checkpoint("first"));
+        assert_eq!(doc.get_text(), "one");
+    }
+
+    #[test]
+    fn test_cached_text_follows_undo_and_redo() {
+        let mut doc = Document::new(Some("one".to_string()));
+        assert_eq!(doc.get_text(), "one");
+        doc.insert(3, " two");
+        assert_eq!(doc.get_text(), "one two");
+        doc.replace(0, 3, "zero");
+        assert_eq!(doc.get_text(), "zero two");
+
+        doc.undo();
+        assert_eq!(doc.get_text(), "one two");
+        doc.undo();
+        assert_eq!(doc.get_text(), "one");
+        doc.redo();
+        assert_eq!(doc.get_text(), "one two");
+        doc.compact();
+        assert_eq!(doc.get_text(), "one two");
+    }
+
+    #[test]
+    fn test_content_hash_ignores_fragmentation() {
+        let whole = Document::new(Some("hello world".to_string()));
+        let mut pieced = Document::new(None);
+        pieced.insert(0, "world");
+        pieced.insert(0, "hello");
+        pieced.insert(5, " ");
+        assert_eq!(pieced.content_hash(), whole.content_hash());
+
+        pieced.insert(5, ",");
+        pieced.delete(5, 1);
+        assert_eq!(pieced.content_hash(), whole.content_hash());
+
+        pieced.delete(0, 1);
+        pieced.insert(0, "j");
+        assert_ne!(pieced.content_hash(), whole.content_hash());
+    }
+
+    #[test]
+    fn test_multibyte_offsets_are_bytes() {
+        let mut doc = Document::new(Some("caf\u{e9}".to_string()));
+        assert_eq!(doc.get_length(), 5);
+
+        // "é" occupies bytes 3..5, so its start and the end are valid insertion points
+        assert!(doc.insert(3, "["));
+        assert!(doc.insert(6, "]"));
+        assert_eq!(doc.get_text(), "caf[\u{e9}]");
+        assert!(!doc.insert(5, "x"));
+        assert_eq!(doc.get_text_range(4, 2), Some("\u{e9}".to_string()));
+        assert_eq!(doc.get_text_range(4, 1), None);
+        assert_eq!(doc.piece_table.offset_to_position(6), Position::new(0, 6));
+    }
+
+    #[test]
+    fn test_mutators_reject_offsets_inside_characters() {
+        let text = "a\u{e9}\u{4e2d}b";
+        let mut doc = Document::new(Some(text.to_string()));
+        for offset in [2, 4, 5] {
+            assert!(!doc.insert(offset, "x"));
+            assert!(!doc.delete(offset, 1));
+            assert!(!doc.replace(offset, 0, "x"));
+        }
+        assert!(!doc.delete(0, 2));
+        assert!(!doc.delete(1, 3));
+        assert!(!doc.replace(3, 1, "x"));
+        assert_eq!(doc.get_text(), text);
+        assert!(doc.validate());
+        assert!(!doc.can_undo());
+
+        assert!(doc.delete(1, 5));
+        assert_eq!(doc.get_text(), "ab");
+    }
+
+    #[test]
+    fn test_emoji_offsets() {
+        let mut doc = Document::new(Some("\u{1f600}\nb".to_string()));
+        for offset in 1..4 {
+            assert!(!doc.insert(offset, "x"));
+            assert!(!doc.delete(offset, 1));
+            assert!(!doc.replace(0, offset, "x"));
+        }
+        assert_eq!(doc.get_text(), "\u{1f600}\nb");
+        assert!(!doc.can_undo());
+
+        assert!(doc.insert(4, "!"));
+        assert_eq!(doc.get_text(), "\u{1f600}!\nb");
+        assert_eq!(doc.position_to_offset(0, 2), Some(0));
+        assert_eq!(doc.position_to_offset(1, 0), Some(6));
+        assert!(doc.delete(0, 4));
+        assert_eq!(doc.get_text(), "!\nb");
+    }
+
+    #[test]
+    fn test_find_across_piece_boundary() {
+        let mut doc = Document::new(Some("hello\nwor".to_string()));
+        doc.insert(9, "ld");
+        assert_eq!(doc.piece_table.piece_count(), 2);
+
+        let options = SearchOptions::default();
+        let range = doc.find_range("WORLD", 0, options).unwrap();
+        assert_eq!(range, Range::new(Position::new(1, 0), Position::new(1, 5)));
+        assert_eq!(doc.find_range("world", 7, options), None);
+    }
+
+    #[test]
+    fn test_find_all_overlapping_and_whole_word() {
+        let doc = Document::new(Some("aaa cat concat".to_string()));
+        let exact = SearchOptions { case_sensitive: true, whole_word: false };
+        assert_eq!(doc.find_all_ranges("aa", exact).len(), 1);
+        assert_eq!(
+            doc.find_range("aa", 1, exact),
+            Some(Range::new(Position::new(0, 1), Position::new(0, 3)))
+        );
+
+        assert_eq!(doc.find_all_ranges("cat", exact).len(), 2);
+        let whole_word = SearchOptions { case_sensitive: true, whole_word: true };
+        assert_eq!(
+            doc.find_all_ranges("cat", whole_word),
+            vec![Range::new(Position::new(0, 4), Position::new(0, 7))]
+        );
+    }
+
+    #[test]
+    fn test_replace_all_regex_backreferences() {
+        let mut doc = Document::new(Some("mail alice@example\nand bob@test now".to_string()));
+        assert_eq!(doc.replace_all_regex_matches(r"(\w+)@(\w+)", "$2.$1", ""), Ok(2));
+        assert_eq!(doc.get_text(), "mail example.alice\nand test.bob now");
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "mail alice@example\nand bob@test now");
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_regex_match_ranges_and_errors() {
+        let doc = Document::new(Some("x = 1\nY = 22".to_string()));
+        let matches = doc.regex_match_ranges(r"^([a-z]) = (\d+)$", "im").unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[1].range, Range::from_positions(1, 0, 1, 6));
+        assert_eq!(matches[1].groups[1], Some(Range::from_positions(1, 4, 1, 6)));
+
+        let error = doc.regex_match_ranges("[", "").unwrap_err();
+        assert_eq!(error.kind, RegexErrorKind::InvalidPattern);
+        let mut doc = doc;
+        assert!(doc.replace_all_regex_matches("a", "b", "q").is_err());
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut doc = Document::new(Some("hello".to_string()));
+        doc.insert(5, " world");
+        doc.delete(0, 1);
+        doc.insert(0, "J");
+        doc.undo();
+
+        let restored = Document::from_json(&doc.serialize()).unwrap();
+        assert_eq!(restored.get_text(), doc.get_text());
+        assert_eq!(restored.can_undo(), doc.can_undo());
+        assert_eq!(restored.can_redo(), doc.can_redo());
+
+        let mut restored = restored;
+        assert!(restored.redo());
+        assert_eq!(restored.get_text(), "Jello world");
+        assert!(restored.undo() && restored.undo() && restored.undo());
+        assert_eq!(restored.get_text(), "hello");
+        assert_eq!(restored.get_line_offset(0), Some(0));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_bad_input() {
+        let json = Document::new(Some("abc".to_string())).serialize();
+        assert!(Document::from_json("not json").is_err());
+        let future = json.replacen("\"version\":1", "\"version\":2", 1);
+        assert_eq!(
+            Document::from_json(&future).err().as_deref(),
+            Some("unsupported document format version 2")
+        );
+        let corrupted = json.replacen("\"total_length\":3", "\"total_length\":4", 1);
+        assert!(Document::from_json(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_compact_keeps_history() {
+        let mut doc = Document::new(Some("abc".to_string()));
+        doc.insert(3, &"long text ".repeat(100));
+        doc.delete(3, 1000);
+        let state = doc.capture_state();
+
+        assert_eq!(doc.compact(), 1000);
+        assert_eq!(doc.get_text(), "abc");
+        assert!(!doc.restore_state(state));
+        assert!(doc.undo());
+        assert_eq!(doc.get_text().len(), 1003);
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "abc");
+    }
+
+    #[test]
+    fn test_position_based_editing() {
+        let mut doc = Document::new(Some("first\nsecond\nthird\nfourth".to_string()));
+        assert!(doc.insert_at(1, 3, "-"));
+        assert_eq!(doc.get_line(1).as_deref(), Some("sec-ond"));
+        assert!(doc.insert_at(0, 99, "!"));
+        assert!(doc.insert_at(99, 0, "?"));
+        assert_eq!(doc.get_text(), "first!\nsec-ond\nthird\nfourth?");
+
+        let range = Range::from_positions(0, 2, 2, 3);
+        let (start, end) = doc.position_range_offsets(range);
+        assert_eq!(doc.piece_table.get_text_range(start, end - start), "rst!\nsec-ond\nthi");
+        assert!(doc.delete_position_range(range));
+        assert_eq!(doc.get_text(), "fird\nfourth?");
+        assert_eq!(doc.get_line_count(), 2);
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_line_count(), 4);
+        assert!(doc.delete_position_range(Range::from_positions(9, 9, 3, 2)));
+        assert_eq!(doc.get_text(), "first!\nsec-ond\nthird\nfo");
+    }
+
+    #[test]
+    fn test_set_text_resets_pieces_and_undoes() {
+        let mut doc = Document::new(Some("old".to_string()));
+        for i in 0..10 {
+            doc.insert(0, &i.to_string());
+        }
+        let before = doc.get_text();
+        let cursor = doc.char_cursor(0);
+
+        doc.set_text("brand\nnew");
+        assert_eq!(doc.get_text(), "brand\nnew");
+        assert_eq!(doc.piece_table.piece_count(), 1);
+        assert_eq!(doc.get_line_count(), 2);
+        assert!(!cursor.is_valid(&doc));
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), before);
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "brand\nnew");
+    }
+
+    #[test]
+    fn test_read_only_rejects_mutations() {
+        let mut doc = Document::new(Some("keep me".to_string()));
+        doc.insert(0, ">> ");
+        doc.set_read_only(true);
+        assert!(doc.is_read_only());
+
+        assert!(!doc.insert(0, "x"));
+        assert!(!doc.delete(0, 2));
+        assert!(!doc.replace(0, 2, "y"));
+        assert!(!doc.set_text("gone"));
+        assert!(!doc.undo());
+        assert_eq!(doc.replace_all_whole_word("me", "you", true), 0);
+        assert_eq!(doc.replace_all_regex_matches("e", "E", ""), Ok(0));
+        assert_eq!(doc.get_text(), ">> keep me");
+        assert_eq!(doc.get_line(0).as_deref(), Some(">> keep me"));
+        assert_eq!(doc.find_all_ranges("e", SearchOptions::default()).len(), 3);
+
+        doc.set_read_only(false);
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "keep me");
+    }
+
+    #[test]
+    fn test_word_navigation_across_pieces() {
+        let mut doc = Document::new(Some("foo_bar baz".to_string()));
+        doc.insert(11, ".qux");
+        doc.insert(4, "x");
+        assert_eq!(doc.get_text(), "foo_xbar baz.qux");
+        assert_eq!(doc.next_word_boundary(0), 8);
+        assert_eq!(doc.next_word_boundary(8), 12);
+        assert_eq!(doc.prev_word_boundary(16), 13);
+        assert_eq!(doc.prev_word_boundary(99), 13);
+        assert_eq!(doc.word_range(6), Range::from_positions(0, 0, 0, 8));
+    }
+
+    #[test]
+    fn test_range_stats() {
+        let mut doc = Document::new(Some("one two\nthree".to_string()));
+        doc.insert(6, "o");
+        let stats = doc.range_stats(Range::from_positions(0, 4, 1, 3));
+        assert_eq!(stats, DocumentStats { bytes: 8, chars: 8, words: 2, lines: 2 });
+        assert_eq!(DocumentStats::from_chunks(doc.piece_table.chunks()).words, 3);
+    }
+
+    #[test]
+    fn test_overflowing_lengths_are_rejected() {
+        let mut doc = Document::new(Some("x\u{1f600}y".to_string()));
+        assert!(!doc.delete(1, usize::MAX));
+        assert!(!doc.replace(usize::MAX, 2, "z"));
+        assert!(!doc.delete(2, 1));
+        assert_eq!(doc.get_text_range(1, usize::MAX), None);
+        assert_eq!(doc.get_text_range_clamped(usize::MAX, usize::MAX), "");
+        assert!(!doc.delete_utf16(usize::MAX, 1));
+        assert_eq!(doc.get_text(), "x\u{1f600}y");
+    }
+
+    #[test]
+    fn test_change_events() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut doc = Document::new(Some("start".to_string()));
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&events);
+        let id = doc.add_change_listener(move |event| recorded.borrow_mut().push(event.clone()));
+        let second = Rc::new(RefCell::new(0));
+        let counter = Rc::clone(&second);
+        doc.add_change_listener(move |_| *counter.borrow_mut() += 1);
+
+        doc.insert(5, "\nmore\nlines");
+        assert_eq!(
+            events.borrow()[0],
+            ChangeEvent {
+                op_type: OperationType::Insert,
+                offset: 5,
+                removed_length: 0,
+                inserted_text: "\nmore\nlines".to_string(),
+                line_delta: 2,
+            }
+        );
+
+        doc.undo();
+        assert_eq!(events.borrow()[1].op_type, OperationType::Delete);
+        assert_eq!(events.borrow()[1].line_delta, -2);
+        doc.replace(0, 1, "S");
+        assert_eq!(events.borrow()[2].op_type, OperationType::Replace);
+
+        assert!(doc.off_change(id));
+        assert!(!doc.off_change(id));
+        doc.redo();
+        doc.insert(0, "x");
+        assert_eq!(events.borrow().len(), 3);
+        assert_eq!(*second.borrow(), 4);
+    }
+
+    #[test]
+    fn test_diff_touches_only_changed_line() {
+        let mut doc = Document::new(Some("first\nsecond\nthird\n".to_string()));
+        let target = "first\nSECOND!\nthird\n";
+        let operations = diff::line_diff(&doc.get_text(), target);
+        assert_eq!(
+            operations,
+            vec![
+                Operation::delete(6, "second\n".to_string()),
+                Operation::insert(6, "SECOND!\n".to_string()),
+            ]
+        );
+
+        assert!(doc.apply_operations(&operations));
+        assert_eq!(doc.get_text(), target);
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "first\nsecond\nthird\n");
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_apply_operations_rejects_mismatch() {
+        let mut doc = Document::new(Some("abc".to_string()));
+        let operations = vec![Operation::insert(0, "x".to_string()), Operation::delete(1, "zz".to_string())];
+        assert!(!doc.apply_operations(&operations));
+        assert!(!doc.apply_operations(&[Operation::insert(9, "x".to_string())]));
+        assert_eq!(doc.get_text(), "abc");
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_delete_line() {
+        let mut doc = Document::new(Some("one\ntwo\nthree".to_string()));
+        assert!(doc.delete_line(1));
+        assert_eq!(doc.get_text(), "one\nthree");
+        assert!(doc.delete_line(1));
+        assert_eq!(doc.get_text(), "one");
+        assert!(doc.delete_line(0));
+        assert_eq!(doc.get_text(), "");
+        assert!(!doc.delete_line(3));
+
+        doc.undo();
+        doc.undo();
+        assert_eq!(doc.get_text(), "one\nthree");
+    }
+
+    #[test]
+    fn test_duplicate_last_line_without_newline() {
+        let mut doc = Document::new(Some("one\ntwo".to_string()));
+        assert!(doc.duplicate_line(1));
+        assert_eq!(doc.get_text(), "one\ntwo\ntwo");
+        assert!(doc.duplicate_line(0));
+        assert_eq!(doc.get_text(), "one\none\ntwo\ntwo");
+        assert!(!doc.duplicate_line(9));
+        doc.undo();
+        assert_eq!(doc.get_text(), "one\ntwo\ntwo");
+    }
+
+    #[test]
+    fn test_move_line() {
+        let mut doc = Document::new(Some("a\nb\nc".to_string()));
+        assert!(!doc.move_line(0, -1));
+        assert!(!doc.can_undo());
+
+        assert!(doc.move_line(0, 1));
+        assert_eq!(doc.get_text(), "b\na\nc");
+        assert!(doc.move_line(1, 1));
+        assert_eq!(doc.get_text(), "b\nc\na");
+        assert!(!doc.move_line(2, 1));
+        assert!(doc.move_line(2, -1));
+        assert_eq!(doc.get_text(), "b\na\nc");
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "b\nc\na");
+    }
+
+    #[test]
+    fn test_trim_trailing_whitespace() {
+        let original = "keep  \n\t\n\nline\t \r\nlast ";
+        let mut doc = Document::new(Some(original.to_string()));
+        assert_eq!(doc.trim_trailing_whitespace(), 4);
+        assert_eq!(doc.get_text(), "keep\n\n\nline\r\nlast");
+        assert_eq!(doc.get_line_count(), 5);
+        assert_eq!(doc.trim_trailing_whitespace(), 0);
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), original);
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_visual_columns() {
+        let doc = Document::new(Some("x\na\tb".to_string()));
+        assert_eq!(doc.visual_column(4, 4), 4);
+        assert_eq!(doc.visual_column(99, 4), 5);
+        assert_eq!(doc.visual_column_to_offset(1, 4, 4), Some(4));
+        assert_eq!(doc.visual_column_to_offset(1, 3, 4), Some(3));
+        assert_eq!(doc.visual_column_to_offset(1, 50, 4), Some(5));
+        assert_eq!(doc.visual_column_to_offset(2, 0, 4), None);
+    }
+
+    #[test]
+    fn test_marker_insert_at_marker() {
+        let mut doc = Document::new(Some("ab".to_string()));
+        let left = doc.create_marker(1, MarkerBias::Left).unwrap();
+        let right = doc.create_marker(1, MarkerBias::Right).unwrap();
+        doc.insert(1, "xyz");
+        assert_eq!(doc.get_marker_offset(left), Some(1));
+        assert_eq!(doc.get_marker_offset(right), Some(4));
+
+        doc.undo();
+        assert_eq!(doc.get_marker_offset(right), Some(1));
+        assert!(doc.remove_marker(left));
+        assert_eq!(doc.get_marker_offset(left), None);
+        assert!(!doc.remove_marker(left));
+    }
+
+    #[test]
+    fn test_marker_deletes_and_replace() {
+        let mut doc = Document::new(Some("one two three".to_string()));
+        let spanned = doc.create_marker(5, MarkerBias::Right).unwrap();
+        let after = doc.create_marker(8, MarkerBias::Left).unwrap();
+        doc.delete(4, 3);
+        assert_eq!(doc.get_marker_offset(spanned), Some(4));
+        assert_eq!(doc.get_marker_offset(after), Some(5));
+
+        doc.delete(0, 2);
+        assert_eq!(doc.get_marker_offset(after), Some(3));
+
+        doc.replace(0, 3, "ONE!");
+        assert_eq!(doc.get_marker_offset(after), Some(4));
+        assert_eq!(doc.create_marker(99, MarkerBias::Left), None);
+    }
+
+    #[test]
+    fn test_text_between_markers_follows_edits() {
+        let mut doc = Document::new(Some("head [body] tail".to_string()));
+        let open = doc.create_marker(6, MarkerBias::Left).unwrap();
+        let close = doc.create_marker(10, MarkerBias::Right).unwrap();
+        assert_eq!(doc.text_between_markers(open, close).as_deref(), Some("body"));
+        assert_eq!(doc.text_between_markers(close, open).as_deref(), Some("body"));
+
+        doc.insert(8, "--");
+        doc.insert(0, ">> ");
+        doc.delete(doc.get_length() - 4, 4);
+        assert_eq!(doc.text_between_markers(open, close).as_deref(), Some("bo--dy"));
+
+        doc.replace(9, 2, "DY");
+        assert_eq!(doc.text_between_markers(open, close).as_deref(), Some("DY--dy"));
+
+        assert!(doc.remove_marker(close));
+        assert_eq!(doc.text_between_markers(open, close), None);
+    }
+
+    #[test]
+    fn test_transaction_undo_and_redo() {
+        let mut doc = Document::new(Some("world".to_string()));
+        doc.begin_transaction();
+        doc.insert(0, "hello ");
+        doc.delete(6, 5);
+        doc.insert(6, "there");
+        assert!(!doc.can_undo());
+        assert!(doc.end_transaction());
+        assert_eq!(doc.get_text(), "hello there");
+        assert!(doc.can_undo());
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_text(), "world");
+        assert!(!doc.can_undo());
+
+        assert!(doc.redo());
+        assert_eq!(doc.get_text(), "hello there");
+        assert!(!doc.can_redo());
+    }
+
+    #[test]
+    fn test_utf16_editing() {
+        let mut doc = Document::new(Some("a\u{1f600}b\nc\u{e9}d".to_string()));
+        assert_eq!(doc.get_length_utf16(), 8);
+        assert_eq!(doc.get_length(), 11);
+
+        assert!(!doc.insert_utf16(2, "x"));
+        assert!(doc.insert_utf16(3, "x"));
+        assert_eq!(doc.get_text(), "a\u{1f600}xb\nc\u{e9}d");
+
+        assert!(!doc.delete_utf16(1, 1));
+        assert!(doc.delete_utf16(1, 2));
+        assert_eq!(doc.get_text(), "axb\nc\u{e9}d");
+        assert!(!doc.delete_utf16(0, usize::MAX));
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        let doc = Document::new(Some("\u{1f600}\u{1f600}\nx\u{1f600}y".to_string()));
+        assert_eq!(doc.utf16_position_at(4), Some(Position::new(0, 4)));
+        assert_eq!(doc.utf16_position_at(5), Some(Position::new(1, 0)));
+        assert_eq!(doc.utf16_position_at(8), Some(Position::new(1, 3)));
+        assert_eq!(doc.utf16_position_at(7), None);
+        assert_eq!(doc.utf16_position_at(10), None);
+
+        assert_eq!(doc.position_to_offset_utf16(1, 3), Some(8));
+        assert_eq!(doc.position_to_offset_utf16(1, 2), Some(6));
+        assert_eq!(doc.position_to_offset_utf16(0, 99), Some(4));
+        assert_eq!(doc.position_to_offset_utf16(2, 0), None);
+    }
+
+    #[test]
+    fn test_block_comment_toggle() {
+        let mut doc = Document::new(Some("fn a() {\n    b();\n}\n".to_string()));
+        assert!(doc.block_comment(0, 19, "/*", "*/"));
+        assert_eq!(doc.get_text(), "/*fn a() {\n    b();\n}*/\n");
+
+        assert!(doc.block_comment(0, 23, "/*", "*/"));
+        assert_eq!(doc.get_text(), "fn a() {\n    b();\n}\n");
+        assert!(doc.can_undo());
+    }
+
+    #[test]
+    fn test_block_comment_surrounding_markers() {
+        let mut doc = Document::new(Some("x /*line1\nline2*/ y".to_string()));
+        assert!(doc.block_comment(4, 15, "/*", "*/"));
+        assert_eq!(doc.get_text(), "x line1\nline2 y");
+
+        doc.undo();
+        assert_eq!(doc.get_text(), "x /*line1\nline2*/ y");
+    }
+
+    #[test]
+    fn test_batch_conversions_reject_malformed_input() {
+        use serde_json::json;
+
+        let doc = Document::new(Some("ab\ncd".to_string()));
+        assert_eq!(
+            doc.decode_offsets_to_positions(json!([4, 0])).unwrap(),
+            vec![Position::new(1, 1), Position::new(0, 0)]
+        );
+        assert_eq!(
+            doc.decode_positions_to_offsets(json!([{ "line": 1, "column": 1 }, { "line": 9, "column": 0 }]))
+                .unwrap(),
+            vec![Some(4), None]
+        );
+        assert!(doc.decode_offsets_to_positions(json!([])).unwrap().is_empty());
+
+        assert!(doc.decode_offsets_to_positions(json!(3)).is_err());
+        assert!(doc.decode_offsets_to_positions(json!([1, -2])).is_err());
+        assert!(doc.decode_offsets_to_positions(json!([1.5])).is_err());
+        assert!(doc.decode_positions_to_offsets(json!({ "line": 0, "column": 0 })).is_err());
+        assert!(doc.decode_positions_to_offsets(json!([{ "column": 1 }])).is_err());
+    }
+
+    #[test]
+    fn test_block_comment_rejects_offsets_inside_characters() {
+        let mut doc = Document::new(Some("let caf\u{e9} = 1;".to_string()));
+        assert!(!doc.block_comment(4, 8, "/*", "*/"));
+        assert!(!doc.block_comment(8, 9, "/*", "*/"));
+        assert_eq!(doc.get_text(), "let caf\u{e9} = 1;");
+        assert!(!doc.can_undo());
+
+        assert!(doc.block_comment(4, 9, "/*", "*/"));
+        assert_eq!(doc.get_text(), "let /*caf\u{e9}*/ = 1;");
+    }
 }
@@ -0,0 +1,72 @@
+use wasm_bindgen::prelude::*;
+
+/// Which side of an insertion a marker sticks to when text is inserted exactly at it
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerBias {
+    /// Stay before the inserted text
+    Left,
+    /// Move past the inserted text
+    Right,
+}
+
+/// A byte offset that follows the text around it as the document is edited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker {
+    pub offset: usize,
+    pub bias: MarkerBias,
+}
+
+impl Marker {
+    pub fn new(offset: usize, bias: MarkerBias) -> Self {
+        Marker { offset, bias }
+    }
+
+    /// Update the offset for `removed` bytes at `offset` being replaced by `inserted` bytes
+    /// Markers inside the removed range collapse to the edit and then follow their
+    /// bias; a marker at the end of a removed range stays after the new text
+    pub fn adjust(&mut self, offset: usize, removed: usize, inserted: usize) {
+        let end = offset + removed;
+        if self.offset < offset {
+            return;
+        }
+        if self.offset > end || (removed > 0 && self.offset == end) {
+            self.offset = self.offset - removed + inserted;
+            return;
+        }
+        self.offset = match self.bias {
+            MarkerBias::Left => offset,
+            MarkerBias::Right => offset + inserted,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adjust_insert_at_marker_follows_bias() {
+        let mut left = Marker::new(4, MarkerBias::Left);
+        let mut right = Marker::new(4, MarkerBias::Right);
+        left.adjust(4, 0, 3);
+        right.adjust(4, 0, 3);
+        assert_eq!(left.offset, 4);
+        assert_eq!(right.offset, 7);
+    }
+
+    #[test]
+    fn test_adjust_delete() {
+        let mut spanned = Marker::new(5, MarkerBias::Right);
+        spanned.adjust(2, 6, 0);
+        assert_eq!(spanned.offset, 2);
+
+        let mut after = Marker::new(10, MarkerBias::Left);
+        after.adjust(2, 6, 0);
+        assert_eq!(after.offset, 4);
+
+        let mut before = Marker::new(1, MarkerBias::Right);
+        before.adjust(2, 6, 0);
+        assert_eq!(before.offset, 1);
+    }
+}
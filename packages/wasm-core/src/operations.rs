@@ -6,16 +6,18 @@ pub enum OperationType {
     Insert,
     Delete,
     Replace,
+    /// A group of operations undone and redone as one step
+    Compound,
 }
 
 /// Represents a single edit operation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Operation {
     /// Type of operation
     pub op_type: OperationType,
-    /// Character offset where the operation occurred
+    /// Byte offset where the operation occurred
     pub offset: usize,
-    /// Length of the affected text
+    /// Length of the affected text in bytes
     pub length: usize,
     /// The text involved in the operation
     /// - For Insert: the inserted text
@@ -24,6 +26,35 @@ pub struct Operation {
     pub text: String,
     /// For Replace operations: the old text that was replaced
     pub old_text: Option<String>,
+    /// For Compound operations: the grouped operations in the order they were applied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub operations: Vec<Operation>,
+}
+
+/// Short description of an operation for UI labels such as "Undo Insert"
+/// Serialized as `{ opType, offset, length }`, where `length` is the number of
+/// bytes removed, or inserted for an insert; compound operations report the
+/// offset of their first part and a length of 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationSummary {
+    pub op_type: OperationType,
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl From<&Operation> for OperationSummary {
+    fn from(operation: &Operation) -> Self {
+        let length = match operation.op_type {
+            OperationType::Insert => operation.inserted_length(),
+            _ => operation.removed_length(),
+        };
+        OperationSummary {
+            op_type: operation.op_type,
+            offset: operation.offset,
+            length,
+        }
+    }
 }
 
 impl Operation {
@@ -34,6 +65,7 @@ impl Operation {
             length,
             text,
             old_text: None,
+            operations: Vec::new(),
         }
     }
 
@@ -44,6 +76,7 @@ impl Operation {
             length: delete_length,
             text: new_text,
             old_text: Some(old_text),
+            operations: Vec::new(),
         }
     }
 
@@ -58,4 +91,251 @@ impl Operation {
         let length = deleted_text.len();
         Operation::new(OperationType::Delete, offset, length, deleted_text)
     }
+
+    /// Create a compound operation from operations in the order they were applied
+    /// Nested compound operations are flattened into the new group
+    pub fn compound(operations: Vec<Operation>) -> Self {
+        let mut flattened = Vec::with_capacity(operations.len());
+        for operation in operations {
+            if operation.op_type == OperationType::Compound {
+                flattened.extend(operation.operations);
+            } else {
+                flattened.push(operation);
+            }
+        }
+
+        Operation {
+            op_type: OperationType::Compound,
+            offset: flattened.first().map_or(0, |op| op.offset),
+            length: 0,
+            text: String::new(),
+            old_text: None,
+            operations: flattened,
+        }
+    }
+
+    /// Number of bytes the operation removes at its offset
+    /// Zero for inserts and for compound operations, whose parts carry their own lengths
+    pub fn removed_length(&self) -> usize {
+        match self.op_type {
+            OperationType::Delete | OperationType::Replace => self.length,
+            OperationType::Insert | OperationType::Compound => 0,
+        }
+    }
+
+    /// Number of bytes the operation inserts at its offset
+    /// This is the piece table's unit, so undoing a replace removes exactly the
+    /// new text even when its char count differs. Zero for deletes and compounds
+    pub fn inserted_length(&self) -> usize {
+        match self.op_type {
+            OperationType::Insert | OperationType::Replace => self.text.len(),
+            OperationType::Delete | OperationType::Compound => 0,
+        }
+    }
+
+    /// Approximate heap bytes owned by the operation's texts and grouped parts
+    /// Excludes the `Operation` itself, which lives in its container
+    pub fn heap_bytes(&self) -> usize {
+        self.text.capacity()
+            + self.old_text.as_ref().map_or(0, String::capacity)
+            + self.operations.capacity() * size_of::<Operation>()
+            + self.operations.iter().map(Operation::heap_bytes).sum::<usize>()
+    }
+
+    /// Rebase this operation so it applies after `other`, which was applied concurrently
+    /// `priority` decides two inserts at the same offset: the side with priority
+    /// stays on the left. Applying `a` then `b.transform(&a, !p)` gives the same text
+    /// as applying `b` then `a.transform(&b, p)`. A delete split by an insert becomes
+    /// a compound of two deletes; replaces are treated as a delete then an insert
+    pub fn transform(&self, other: &Operation, priority: bool) -> Operation {
+        if let Some(parts) = self.primitive_parts() {
+            let mut against = other.clone();
+            let mut transformed = Vec::with_capacity(parts.len());
+            for part in parts {
+                let rebased = part.transform(&against, priority);
+                against = against.transform(&part, !priority);
+                transformed.push(rebased);
+            }
+            return Operation::compound(transformed);
+        }
+        if let Some(parts) = other.primitive_parts() {
+            return parts.iter().fold(self.clone(), |op, part| op.transform(part, priority));
+        }
+
+        match (self.op_type, other.op_type) {
+            (OperationType::Insert, OperationType::Insert) => {
+                let other_first = other.offset < self.offset || (other.offset == self.offset && !priority);
+                let offset = if other_first { self.offset + other.length } else { self.offset };
+                Operation::insert(offset, self.text.clone())
+            }
+            (OperationType::Insert, _) => {
+                let offset = if self.offset <= other.offset {
+                    self.offset
+                } else {
+                    self.offset.saturating_sub(other.length).max(other.offset)
+                };
+                Operation::insert(offset, self.text.clone())
+            }
+            (_, OperationType::Insert) => {
+                let end = self.offset + self.length;
+                if other.offset <= self.offset {
+                    Operation::delete(self.offset + other.length, self.text.clone())
+                } else if other.offset >= end {
+                    self.clone()
+                } else {
+                    // The insert lands inside the deleted range, so delete around it
+                    let split = other.offset - self.offset;
+                    Operation::compound(vec![
+                        Operation::delete(other.offset + other.length, self.text[split..].to_string()),
+                        Operation::delete(self.offset, self.text[..split].to_string()),
+                    ])
+                }
+            }
+            _ => {
+                let end = self.offset + self.length;
+                let other_end = other.offset + other.length;
+                let overlap_start = self.offset.max(other.offset);
+                let overlap_end = end.min(other_end);
+                let mut text = self.text.clone();
+                if overlap_start < overlap_end {
+                    text.replace_range(overlap_start - self.offset..overlap_end - self.offset, "");
+                }
+                let offset = if self.offset <= other.offset {
+                    self.offset
+                } else {
+                    self.offset.saturating_sub(other.length).max(other.offset)
+                };
+                Operation::delete(offset, text)
+            }
+        }
+    }
+
+    /// Rebase two concurrent operations against each other
+    /// Returns `(a', b')` where `a'` applies after `b` and `b'` after `a`; `a` wins ties
+    pub fn transform_pair(a: &Operation, b: &Operation) -> (Operation, Operation) {
+        (a.transform(b, true), b.transform(a, false))
+    }
+
+    /// Split a replace or compound operation into the inserts and deletes it applies
+    fn primitive_parts(&self) -> Option<Vec<Operation>> {
+        match self.op_type {
+            OperationType::Insert | OperationType::Delete => None,
+            OperationType::Replace => Some(vec![
+                Operation::delete(self.offset, self.old_text.clone().unwrap_or_default()),
+                Operation::insert(self.offset, self.text.clone()),
+            ]),
+            OperationType::Compound => Some(self.operations.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str, operation: &Operation) -> String {
+        let mut result = text.to_string();
+        match operation.op_type {
+            OperationType::Insert => result.insert_str(operation.offset, &operation.text),
+            OperationType::Delete => {
+                assert_eq!(&result[operation.offset..operation.offset + operation.length], operation.text);
+                result.replace_range(operation.offset..operation.offset + operation.length, "");
+            }
+            OperationType::Replace => {
+                let old_text = operation.old_text.as_deref().unwrap_or_default();
+                result.replace_range(operation.offset..operation.offset + old_text.len(), &operation.text);
+            }
+            OperationType::Compound => {
+                return operation.operations.iter().fold(result, |text, op| apply(&text, op));
+            }
+        }
+        result
+    }
+
+    fn assert_converges(text: &str, a: &Operation, b: &Operation) {
+        let (a_prime, b_prime) = Operation::transform_pair(a, b);
+        let a_then_b = apply(&apply(text, a), &b_prime);
+        let b_then_a = apply(&apply(text, b), &a_prime);
+        assert_eq!(a_then_b, b_then_a, "a = {:?}, b = {:?}", a, b);
+    }
+
+    /// Every insert and delete of a short text, including empty and whole-text deletes
+    fn all_operations(text: &str) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        for offset in 0..=text.len() {
+            operations.push(Operation::insert(offset, "XY".to_string()));
+            for end in offset + 1..=text.len() {
+                operations.push(Operation::delete(offset, text[offset..end].to_string()));
+            }
+        }
+        operations
+    }
+
+    #[test]
+    fn test_removed_and_inserted_lengths() {
+        let replace = Operation::new_replace(1, 6, "h\u{e9}llo".to_string(), "hi".to_string());
+        assert_eq!((replace.removed_length(), replace.inserted_length()), (6, 2));
+        let insert = Operation::insert(0, "\u{1f600}".to_string());
+        assert_eq!((insert.removed_length(), insert.inserted_length()), (0, 4));
+        let delete = Operation::delete(0, "ab".to_string());
+        assert_eq!((delete.removed_length(), delete.inserted_length()), (2, 0));
+    }
+
+    #[test]
+    fn test_transform_converges_for_all_pairs() {
+        let text = "abcde";
+        let operations = all_operations(text);
+        for a in &operations {
+            for b in &operations {
+                assert_converges(text, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transform_inserts_at_same_offset_use_priority() {
+        let a = Operation::insert(1, "A".to_string());
+        let b = Operation::insert(1, "B".to_string());
+        let (a_prime, b_prime) = Operation::transform_pair(&a, &b);
+        assert_eq!(apply(&apply("xy", &a), &b_prime), "xABy");
+        assert_eq!(apply(&apply("xy", &b), &a_prime), "xABy");
+
+        let (b_prime, a_prime) = Operation::transform_pair(&b, &a);
+        assert_eq!(apply(&apply("xy", &a), &b_prime), "xBAy");
+        assert_eq!(apply(&apply("xy", &b), &a_prime), "xBAy");
+    }
+
+    #[test]
+    fn test_transform_delete_split_by_insert() {
+        let delete = Operation::delete(1, "bcd".to_string());
+        let insert = Operation::insert(2, "X".to_string());
+        let rebased = delete.transform(&insert, true);
+        assert_eq!(rebased.op_type, OperationType::Compound);
+        assert_eq!(apply("abXcde", &rebased), "aXe");
+        assert_eq!(insert.transform(&delete, false), Operation::insert(1, "X".to_string()));
+    }
+
+    #[test]
+    fn test_transform_overlapping_deletes() {
+        let a = Operation::delete(1, "bcd".to_string());
+        let b = Operation::delete(2, "cdef".to_string());
+        assert_eq!(b.transform(&a, false), Operation::delete(1, "ef".to_string()));
+        assert_eq!(a.transform(&b, true), Operation::delete(1, "b".to_string()));
+        assert_converges("abcdefg", &a, &b);
+    }
+
+    #[test]
+    fn test_transform_replace_and_compound() {
+        let text = "hello world";
+        let replace = Operation::new_replace(0, 5, "hello".to_string(), "howdy".to_string());
+        let compound = Operation::compound(vec![
+            Operation::insert(11, "!".to_string()),
+            Operation::delete(5, " ".to_string()),
+        ]);
+        for other in all_operations(text) {
+            assert_converges(text, &replace, &other);
+            assert_converges(text, &other, &compound);
+        }
+        assert_converges(text, &replace, &compound);
+    }
 }
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::operations::{Operation, OperationType};
+
+/// Kind of change described by a patch entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PatchOp {
+    Insert,
+    Delete,
+    Replace,
+}
+
+/// Unit in which a patch entry's offset is expressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OffsetUnit {
+    /// UTF-8 byte offset, as used natively by the document
+    #[default]
+    Byte,
+    /// Unicode scalar value (char) offset
+    Char,
+}
+
+/// A single entry of the portable JSON patch log
+///
+/// Serialized as `{ "op", "offset", "unit", "removed", "inserted" }`. `removed`
+/// is the exact text expected at `offset` and is checked before applying, so a
+/// patch only applies to the document state it was recorded against.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchEntry {
+    pub op: PatchOp,
+    pub offset: usize,
+    #[serde(default)]
+    pub unit: OffsetUnit,
+    #[serde(default)]
+    pub removed: String,
+    #[serde(default)]
+    pub inserted: String,
+}
+
+impl PatchEntry {
+    /// Convert a single (non-compound) history operation into a byte-offset patch entry
+    pub fn from_operation(operation: &Operation) -> Self {
+        let (op, removed, inserted) = match operation.op_type {
+            OperationType::Insert => (PatchOp::Insert, String::new(), operation.text.clone()),
+            OperationType::Delete => (PatchOp::Delete, operation.text.clone(), String::new()),
+            OperationType::Replace | OperationType::Compound => (
+                PatchOp::Replace,
+                operation.old_text.clone().unwrap_or_default(),
+                operation.text.clone(),
+            ),
+        };
+        PatchEntry {
+            op,
+            offset: operation.offset,
+            unit: OffsetUnit::Byte,
+            removed,
+            inserted,
+        }
+    }
+}
+
+/// Convert operations, oldest first, into patch entries
+/// Compound operations are expanded into their grouped operations
+pub fn from_operations<'a>(operations: impl IntoIterator<Item = &'a Operation>) -> Vec<PatchEntry> {
+    let mut entries = Vec::new();
+    for operation in operations {
+        if operation.op_type == OperationType::Compound {
+            entries.extend(from_operations(&operation.operations));
+        } else {
+            entries.push(PatchEntry::from_operation(operation));
+        }
+    }
+    entries
+}
+
+/// Convert a char offset into a byte offset within the text made of `chunks`
+/// Stops at the chunk holding the offset instead of materializing the text
+pub fn char_to_byte_offset<'a>(chunks: impl IntoIterator<Item = &'a str>, char_offset: usize) -> Option<usize> {
+    let mut chars_before = 0;
+    let mut bytes_before = 0;
+    for chunk in chunks {
+        if let Some((index, _)) = chunk.char_indices().nth(char_offset - chars_before) {
+            return Some(bytes_before + index);
+        }
+        chars_before += chunk.chars().count();
+        bytes_before += chunk.len();
+    }
+    (chars_before == char_offset).then_some(bytes_before)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_operation_shapes() {
+        let replace = Operation::new_replace(2, 3, "old".to_string(), "new!".to_string());
+        assert_eq!(
+            PatchEntry::from_operation(&replace),
+            PatchEntry {
+                op: PatchOp::Replace,
+                offset: 2,
+                unit: OffsetUnit::Byte,
+                removed: "old".to_string(),
+                inserted: "new!".to_string(),
+            }
+        );
+
+        let delete = Operation::delete(1, "x".to_string());
+        let entry = PatchEntry::from_operation(&delete);
+        assert_eq!(entry.op, PatchOp::Delete);
+        assert_eq!(entry.removed, "x");
+        assert!(entry.inserted.is_empty());
+    }
+
+    #[test]
+    fn test_char_to_byte_offset() {
+        assert_eq!(char_to_byte_offset(["caf\u{e9}!"], 4), Some(5));
+        assert_eq!(char_to_byte_offset(["caf\u{e9}!"], 5), Some(6));
+        assert_eq!(char_to_byte_offset(["caf\u{e9}!"], 6), None);
+        assert_eq!(char_to_byte_offset(["ca", "f\u{e9}", "!"], 3), Some(3));
+        assert_eq!(char_to_byte_offset(["ca", "f\u{e9}", "!"], 4), Some(5));
+        assert_eq!(char_to_byte_offset(["ca", "f\u{e9}", "!"], 5), Some(6));
+        assert_eq!(char_to_byte_offset(["ca", "", "f\u{e9}"], 4), Some(5));
+        assert_eq!(char_to_byte_offset(Vec::<&str>::new(), 0), Some(0));
+    }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::history::History;
+use crate::piece_table::PieceTable;
+
+/// Version tag written by `serialize`; bump it when the layout changes
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Borrowed view of the persisted document state, used when serializing
+#[derive(Serialize)]
+struct DocumentStateRef<'a> {
+    version: u32,
+    piece_table: &'a PieceTable,
+    history: &'a History,
+}
+
+/// Owned persisted document state, used when deserializing
+/// The version tag is checked separately before this is parsed
+#[derive(Deserialize)]
+struct DocumentState {
+    piece_table: PieceTable,
+    history: History,
+}
+
+/// Borrowed history-only state, used when exporting undo/redo stacks
+#[derive(Serialize)]
+struct HistoryStateRef<'a> {
+    version: u32,
+    history: &'a History,
+}
+
+/// Owned history-only state, used when importing
+#[derive(Deserialize)]
+struct HistoryState {
+    history: History,
+}
+
+/// Serialize a piece table and history to JSON tagged with the format version
+pub fn to_json(piece_table: &PieceTable, history: &History) -> String {
+    let state = DocumentStateRef {
+        version: FORMAT_VERSION,
+        piece_table,
+        history,
+    };
+    serde_json::to_string(&state).expect("document state always serializes")
+}
+
+/// Parse JSON produced by `to_json`, rejecting unknown versions and corrupted pieces
+pub fn from_json(json: &str) -> Result<(PieceTable, History), String> {
+    check_version(json)?;
+    let state: DocumentState = serde_json::from_str(json).map_err(|error| error.to_string())?;
+    if !state.piece_table.validate() {
+        return Err("piece table failed validation".to_string());
+    }
+    Ok((state.piece_table, state.history))
+}
+
+/// Serialize only the history to JSON tagged with the format version
+pub fn history_to_json(history: &History) -> String {
+    let state = HistoryStateRef {
+        version: FORMAT_VERSION,
+        history,
+    };
+    serde_json::to_string(&state).expect("history always serializes")
+}
+
+/// Parse JSON produced by `history_to_json`, rejecting unknown versions
+/// The operations are not checked against any document here
+pub fn history_from_json(json: &str) -> Result<History, String> {
+    check_version(json)?;
+    let state: HistoryState = serde_json::from_str(json).map_err(|error| error.to_string())?;
+    if state.history.in_transaction() {
+        return Err("history has an open transaction".to_string());
+    }
+    Ok(state.history)
+}
+
+/// Read the version tag first so a newer layout reports its version instead of a parse error
+fn check_version(json: &str) -> Result<(), String> {
+    #[derive(Deserialize)]
+    struct VersionTag {
+        version: u32,
+    }
+    let tag: VersionTag = serde_json::from_str(json).map_err(|error| error.to_string())?;
+    if tag.version != FORMAT_VERSION {
+        return Err(format!("unsupported document format version {}", tag.version));
+    }
+    Ok(())
+}
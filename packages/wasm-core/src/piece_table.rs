@@ -1,14 +1,22 @@
+use std::cell::OnceCell;
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
 use crate::position::Position;
 
+/// Largest document, in bytes, whose line count is recounted after every edit in debug builds
+const DEBUG_RECOUNT_LIMIT: usize = 4 * 1024;
+
 /// Identifies which buffer a piece refers to
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BufferType {
     Original,
     Add,
 }
 
 /// A piece represents a contiguous range of text from one of the buffers
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Piece {
     /// Which buffer this piece refers to
     pub buffer: BufferType,
@@ -45,21 +53,97 @@ impl Piece {
     pub fn line_count(&self) -> usize {
         self.line_starts.len()
     }
+
+    /// Check the cached line starts against a fresh scan of the piece's slice of `buffer`
+    pub fn validate_line_starts(&self, buffer: &str) -> bool {
+        let end = self.start.checked_add(self.length);
+        match end.and_then(|end| buffer.get(self.start..end)) {
+            Some(text) => Self::compute_line_starts(text) == self.line_starts,
+            None => false,
+        }
+    }
 }
 
-/// Piece Table data structure for efficient text editing
+/// Fragmentation diagnostics for deciding when to compact
+/// Serialized as `{ pieceCount, originalLength, addBufferLength, liveAddBytes,
+/// garbageAddBytes, averagePieceLength }`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PieceTableStats {
+    pub piece_count: usize,
+    /// Bytes in the original buffer
+    pub original_length: usize,
+    /// Bytes in the add buffer, including text no longer in the document
+    pub add_buffer_length: usize,
+    /// Add-buffer bytes still referenced by a piece
+    pub live_add_bytes: usize,
+    /// Add-buffer bytes that `compact` would reclaim
+    pub garbage_add_bytes: usize,
+    /// Mean piece length in bytes, or 0 for an empty document
+    pub average_piece_length: f64,
+}
+
+/// Approximate heap bytes held by a document, for hosts budgeting many documents
+/// Serialized as `{ originalBytes, addBufferBytes, piecesBytes, cacheBytes,
+/// historyBytes, totalBytes }`; figures use allocated capacity, not length
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsage {
+    pub original_bytes: usize,
+    /// Includes deleted text until `compact` runs
+    pub add_buffer_bytes: usize,
+    /// The piece list plus each piece's `line_starts`
+    pub pieces_bytes: usize,
+    /// Lazily built line index and materialized text
+    pub cache_bytes: usize,
+    /// Undo and redo stacks, filled in by the document
+    pub history_bytes: usize,
+    pub total_bytes: usize,
+}
+
+impl MemoryUsage {
+    /// Set the history figure and recompute the total
+    pub fn with_history_bytes(mut self, history_bytes: usize) -> Self {
+        self.history_bytes = history_bytes;
+        self.total_bytes = self.original_bytes
+            + self.add_buffer_bytes
+            + self.pieces_bytes
+            + self.cache_bytes
+            + self.history_bytes;
+        self
+    }
+}
+
+/// A captured piece list that shares the piece table's buffers
+/// Both buffers are append-only, so the captured pieces stay valid after later edits
 #[derive(Debug, Clone)]
+pub struct PieceSnapshot {
+    pieces: VecDeque<Piece>,
+    total_length: usize,
+}
+
+/// Piece Table data structure for efficient text editing
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PieceTable {
     /// Original (immutable) buffer containing initial content
     original: String,
     /// Add buffer for all inserted text (append-only)
     add_buffer: String,
     /// List of pieces describing the current document
-    pieces: Vec<Piece>,
+    /// A deque keeps both prepends and appends O(1) amortized
+    pieces: VecDeque<Piece>,
     /// Cached total length
     total_length: usize,
     /// Cached line count
     line_count: usize,
+    /// Incremented on every mutation so readers can detect stale state
+    version: u64,
+    /// Absolute byte offset of every line start, rebuilt lazily after an edit
+    #[serde(skip)]
+    line_index: OnceCell<Vec<usize>>,
+    /// Materialized text, rebuilt lazily after an edit
+    #[serde(skip)]
+    text_cache: OnceCell<String>,
 }
 
 impl PieceTable {
@@ -69,9 +153,9 @@ impl PieceTable {
         let line_count = initial_content.matches('\n').count() + 1;
 
         let pieces = if length > 0 {
-            vec![Piece::new(BufferType::Original, 0, length, &initial_content)]
+            VecDeque::from(vec![Piece::new(BufferType::Original, 0, length, &initial_content)])
         } else {
-            Vec::new()
+            VecDeque::new()
         };
 
         PieceTable {
@@ -80,79 +164,332 @@ impl PieceTable {
             pieces,
             total_length: length,
             line_count,
+            version: 0,
+            line_index: OnceCell::new(),
+            text_cache: OnceCell::new(),
+        }
+    }
+
+    /// Append streamed initial content to the original buffer and the end of the document
+    /// Only the new text is scanned for line starts, and consecutive chunks extend
+    /// one piece, so streaming builds the same table as `new` on the concatenation
+    pub fn append_original(&mut self, chunk: &str) {
+        if chunk.is_empty() {
+            return;
         }
+
+        let start = self.original.len();
+        self.original.push_str(chunk);
+        let new_piece = Piece::new(BufferType::Original, start, chunk.len(), chunk);
+        let new_lines = new_piece.line_count();
+
+        let extended = match self.pieces.len() {
+            0 => false,
+            len => self.extend_piece(len - 1, &new_piece),
+        };
+        if !extended {
+            self.pieces.push_back(new_piece);
+        }
+
+        self.total_length += chunk.len();
+        self.line_count += new_lines;
+        self.version += 1;
+        self.invalidate_caches();
     }
 
     /// Get the full text content
     pub fn get_text(&self) -> String {
-        let mut result = String::with_capacity(self.total_length);
-        for piece in &self.pieces {
-            let buffer = match piece.buffer {
-                BufferType::Original => &self.original,
-                BufferType::Add => &self.add_buffer,
-            };
-            result.push_str(&buffer[piece.start..piece.start + piece.length]);
+        self.text().to_string()
+    }
+
+    /// Borrow the full text, materializing it once per edit
+    pub fn text(&self) -> &str {
+        self.text_cache.get_or_init(|| {
+            let mut result = String::with_capacity(self.total_length);
+            for piece in &self.pieces {
+                result.push_str(self.piece_text(piece));
+            }
+            result
+        })
+    }
+
+    /// Iterate over the text of each piece in document order
+    pub fn chunks(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.pieces.iter().map(|piece| self.piece_text(piece))
+    }
+
+    /// Iterate over the piece slices overlapping a range, clamped to the document
+    pub fn chunks_in_range(&self, offset: usize, length: usize) -> impl Iterator<Item = &str> {
+        let end = offset.saturating_add(length).min(self.total_length);
+        let mut piece_start = 0;
+        self.pieces.iter().filter_map(move |piece| {
+            let start = piece_start;
+            piece_start += piece.length;
+            let from = offset.max(start);
+            let to = end.min(start + piece.length);
+            (from < to).then(|| &self.piece_text(piece)[from - start..to - start])
+        })
+    }
+
+    /// Length in bytes of the longest common prefix with another piece table
+    /// The content is compared char by char without materializing either text
+    pub fn common_prefix_len(&self, other: &PieceTable) -> usize {
+        self.chunks()
+            .flat_map(str::chars)
+            .zip(other.chunks().flat_map(str::chars))
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum()
+    }
+
+    /// Length in bytes of the longest common suffix with another piece table
+    pub fn common_suffix_len(&self, other: &PieceTable) -> usize {
+        self.chunks()
+            .rev()
+            .flat_map(|chunk| chunk.chars().rev())
+            .zip(other.chunks().rev().flat_map(|chunk| chunk.chars().rev()))
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum()
+    }
+
+    /// Convert a UTF-16 code unit offset into a byte offset
+    /// Returns None if the offset is past the end or falls inside a surrogate pair
+    pub fn utf16_to_offset(&self, utf16_offset: usize) -> Option<usize> {
+        let mut units = 0;
+        let mut offset = 0;
+        for c in self.chunks().flat_map(str::chars) {
+            if units >= utf16_offset {
+                break;
+            }
+            units += c.len_utf16();
+            offset += c.len_utf8();
+        }
+        (units == utf16_offset).then_some(offset)
+    }
+
+    /// Convert a byte offset into a UTF-16 code unit offset
+    /// Offsets past the end are clamped and offsets inside a character snap to its start
+    pub fn offset_to_utf16(&self, offset: usize) -> usize {
+        let offset = self.floor_char_boundary(offset);
+        self.chunks_in_range(0, offset)
+            .flat_map(str::chars)
+            .map(char::len_utf16)
+            .sum()
+    }
+
+    /// Get the length of the document in UTF-16 code units
+    pub fn utf16_length(&self) -> usize {
+        self.chunks().flat_map(str::chars).map(char::len_utf16).sum()
+    }
+
+    /// Replace all content with a fresh single piece, discarding both buffers
+    /// Snapshots taken earlier no longer match the buffers
+    pub fn reset(&mut self, content: String) {
+        let version = self.version + 1;
+        *self = PieceTable::new(content);
+        self.version = version;
+    }
+
+    /// Measure how fragmented the table and its add buffer have become
+    pub fn debug_stats(&self) -> PieceTableStats {
+        let live_add_bytes = self.live_add_bytes();
+        let average_piece_length = if self.pieces.is_empty() {
+            0.0
+        } else {
+            self.total_length as f64 / self.pieces.len() as f64
+        };
+        PieceTableStats {
+            piece_count: self.pieces.len(),
+            original_length: self.original.len(),
+            add_buffer_length: self.add_buffer.len(),
+            live_add_bytes,
+            garbage_add_bytes: self.add_buffer.len() - live_add_bytes,
+            average_piece_length,
+        }
+    }
+
+    /// Estimate the heap bytes held by the buffers, pieces and caches
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let line_starts_bytes: usize = self
+            .pieces
+            .iter()
+            .map(|piece| piece.line_starts.capacity() * size_of::<usize>())
+            .sum();
+        let line_index_bytes = self
+            .line_index
+            .get()
+            .map_or(0, |starts| starts.capacity() * size_of::<usize>());
+        let text_cache_bytes = self.text_cache.get().map_or(0, String::capacity);
+        MemoryUsage {
+            original_bytes: self.original.capacity(),
+            add_buffer_bytes: self.add_buffer.capacity(),
+            pieces_bytes: self.pieces.capacity() * size_of::<Piece>() + line_starts_bytes,
+            cache_bytes: line_index_bytes + text_cache_bytes,
+            history_bytes: 0,
+            total_bytes: 0,
+        }
+        .with_history_bytes(0)
+    }
+
+    /// Bytes of the add buffer referenced by pieces
+    fn live_add_bytes(&self) -> usize {
+        self.pieces
+            .iter()
+            .filter(|piece| piece.buffer == BufferType::Add)
+            .map(|piece| piece.length)
+            .sum()
+    }
+
+    /// Rebuild the add buffer so it only holds bytes referenced by live pieces
+    /// The text is unchanged; snapshots taken earlier no longer match the buffer.
+    /// Returns the number of bytes reclaimed
+    pub fn compact(&mut self) -> usize {
+        let mut compacted = String::with_capacity(self.live_add_bytes());
+
+        for piece in self.pieces.iter_mut().filter(|piece| piece.buffer == BufferType::Add) {
+            let start = compacted.len();
+            compacted.push_str(&self.add_buffer[piece.start..piece.start + piece.length]);
+            piece.start = start;
+        }
+
+        let reclaimed = self.add_buffer.len() - compacted.len();
+        self.add_buffer = compacted;
+        self.text_cache.take();
+        reclaimed
+    }
+
+    /// Capture the current piece list without copying any text
+    pub fn snapshot(&self) -> PieceSnapshot {
+        PieceSnapshot {
+            pieces: self.pieces.clone(),
+            total_length: self.total_length,
+        }
+    }
+
+    /// Materialize the text of a snapshot taken from this piece table
+    pub fn snapshot_text(&self, snapshot: &PieceSnapshot) -> String {
+        let mut result = String::with_capacity(snapshot.total_length);
+        for piece in &snapshot.pieces {
+            result.push_str(self.piece_text(piece));
         }
         result
     }
 
-    /// Get the total length of the document
+    /// Get the total length of the document in bytes
     pub fn get_length(&self) -> usize {
         self.total_length
     }
 
-    /// Get the number of lines in the document
-    pub fn get_line_count(&self) -> usize {
-        self.line_count
+    /// Number of pieces currently describing the document
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len()
     }
 
-    /// Get text in a specific range
-    pub fn get_text_range(&self, offset: usize, length: usize) -> String {
-        if length == 0 {
-            return String::new();
-        }
+    /// Get the mutation counter, which changes whenever the content changes
+    pub fn version(&self) -> u64 {
+        self.version
+    }
 
-        let mut result = String::with_capacity(length);
-        let mut current_offset = 0;
-        let end_offset = offset + length;
+    /// Check whether a byte offset is within the document and on a char boundary
+    /// Every mutator checks its offsets with this so edits never split a character
+    pub fn is_valid_offset(&self, offset: usize) -> bool {
+        offset <= self.total_length && self.floor_char_boundary(offset) == offset
+    }
 
-        for piece in &self.pieces {
-            let piece_end = current_offset + piece.length;
+    /// Check whether a byte range lies within the document with both ends on char boundaries
+    pub fn is_valid_range(&self, offset: usize, length: usize) -> bool {
+        offset
+            .checked_add(length)
+            .is_some_and(|end| self.is_valid_offset(offset) && self.is_valid_offset(end))
+    }
 
-            if piece_end <= offset {
-                current_offset = piece_end;
-                continue;
+    /// Snap an offset back to the nearest char boundary, clamped to the document length
+    pub fn floor_char_boundary(&self, offset: usize) -> usize {
+        match self.find_piece(offset) {
+            Some((index, piece_start)) => {
+                let text = self.piece_text(&self.pieces[index]);
+                let mut local = offset - piece_start;
+                while !text.is_char_boundary(local) {
+                    local -= 1;
+                }
+                piece_start + local
             }
+            None => self.total_length,
+        }
+    }
 
-            if current_offset >= end_offset {
-                break;
-            }
+    /// Locate the piece containing an offset
+    /// Returns the piece index and the offset within that piece; an offset in
+    /// the middle of a character is moved back to the start of that character
+    pub fn locate(&self, offset: usize) -> (usize, usize) {
+        let offset = self.floor_char_boundary(offset);
+        match self.find_piece(offset) {
+            Some((index, piece_start)) => (index, offset - piece_start),
+            None => (self.pieces.len(), 0),
+        }
+    }
 
-            let buffer = match piece.buffer {
-                BufferType::Original => &self.original,
-                BufferType::Add => &self.add_buffer,
-            };
+    /// Read the character at a piece position returned by `locate`
+    /// Returns the character and the position just after it
+    pub fn char_at(&self, piece_index: usize, piece_offset: usize) -> Option<(char, usize, usize)> {
+        let mut index = piece_index;
+        let mut local = piece_offset;
+        while let Some(piece) = self.pieces.get(index) {
+            if local < piece.length {
+                let c = self.piece_text(piece)[local..].chars().next()?;
+                return Some((c, index, local + c.len_utf8()));
+            }
+            index += 1;
+            local = 0;
+        }
+        None
+    }
 
-            let start_in_piece = if offset > current_offset {
-                offset - current_offset
-            } else {
-                0
-            };
+    /// Get the number of lines in the document
+    pub fn get_line_count(&self) -> usize {
+        self.line_count
+    }
 
-            let end_in_piece = if end_offset < piece_end {
-                end_offset - current_offset
-            } else {
-                piece.length
-            };
+    /// Get the text of `length` bytes starting at a byte offset
+    pub fn get_text_range(&self, offset: usize, length: usize) -> String {
+        let start = self.floor_char_boundary(offset);
+        let end = self.floor_char_boundary(offset.saturating_add(length));
+        self.chunks_in_range(start, end - start).collect()
+    }
 
-            let buffer_start = piece.start + start_in_piece;
-            let buffer_end = piece.start + end_in_piece;
-            result.push_str(&buffer[buffer_start..buffer_end]);
+    /// Get `char_length` characters starting at a character offset, clamped to the document
+    /// The pieces are walked directly, so only the slice is copied
+    pub fn char_slice(&self, char_offset: usize, char_length: usize) -> String {
+        self.chunks()
+            .flat_map(str::chars)
+            .skip(char_offset)
+            .take(char_length)
+            .collect()
+    }
 
-            current_offset = piece_end;
+    /// Split the content into chunks of at most `max_chunk_bytes` bytes
+    /// Chunks always end on a char boundary; a single character wider than the
+    /// limit is emitted as its own chunk
+    pub fn to_chunks(&self, max_chunk_bytes: usize) -> Vec<String> {
+        let text = self.get_text();
+        let mut chunks = Vec::new();
+        let mut chunk_start = 0;
+
+        while chunk_start < text.len() {
+            let mut chunk_end = (chunk_start + max_chunk_bytes).min(text.len());
+            while !text.is_char_boundary(chunk_end) {
+                chunk_end -= 1;
+            }
+            if chunk_end == chunk_start {
+                chunk_end = chunk_start
+                    + text[chunk_start..].chars().next().map_or(1, |c| c.len_utf8());
+            }
+            chunks.push(text[chunk_start..chunk_end].to_string());
+            chunk_start = chunk_end;
         }
 
-        result
+        chunks
     }
 
     /// Get a specific line by line number (0-indexed)
@@ -178,35 +515,110 @@ impl PieceTable {
         Some(line_text)
     }
 
-    /// Get the character offset at the start of a line
-    pub fn get_line_offset(&self, line: usize) -> Option<usize> {
-        if line == 0 {
-            return Some(0);
+    /// Get the lines in `start_line..end_line`, without their newlines, from one range read
+    /// `end_line` is clamped to the line count
+    pub fn get_lines(&self, start_line: usize, end_line: usize) -> Vec<String> {
+        let end_line = end_line.min(self.line_count);
+        if start_line >= end_line {
+            return Vec::new();
         }
 
+        let starts = self.line_starts();
+        let start_offset = starts[start_line];
+        let end_offset = starts.get(end_line).map_or(self.total_length, |&offset| offset - 1);
+        self.get_text_range(start_offset, end_offset - start_offset)
+            .split('\n')
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Get the character length of every line, excluding the newline, in one pass
+    pub fn line_lengths(&self) -> Vec<usize> {
+        let mut lengths = Vec::with_capacity(self.line_count);
+        let mut current = 0;
+        for c in self.chunks().flat_map(str::chars) {
+            if c == '\n' {
+                lengths.push(current);
+                current = 0;
+            } else {
+                current += 1;
+            }
+        }
+        lengths.push(current);
+        lengths
+    }
+
+    /// Get the length in bytes of a line, excluding the newline, from the line index alone
+    pub fn line_byte_length(&self, line: usize) -> Option<usize> {
+        let starts = self.line_starts();
+        let start = *starts.get(line)?;
+        let end = starts.get(line + 1).map_or(self.total_length, |&next| next - 1);
+        Some(end - start)
+    }
+
+    /// Get the number of characters in a line, excluding the newline, without copying it
+    pub fn line_char_length(&self, line: usize) -> Option<usize> {
+        let start = self.get_line_offset(line)?;
+        let length = self.line_byte_length(line)?;
+        Some(self.chunks_in_range(start, length).map(|chunk| chunk.chars().count()).sum())
+    }
+
+    /// Get the byte offset at the start of a line
+    pub fn get_line_offset(&self, line: usize) -> Option<usize> {
+        self.line_starts().get(line).copied()
+    }
+
+    /// Get the number of characters (Unicode scalar values) before the start of a line
+    pub fn char_offset_of_line(&self, line: usize) -> Option<usize> {
         if line >= self.line_count {
             return None;
         }
 
         let mut current_line = 0;
-        let mut current_offset = 0;
+        let mut char_offset = 0;
 
         for piece in &self.pieces {
-            for &line_start in &piece.line_starts {
-                current_line += 1;
-                if current_line == line {
-                    return Some(current_offset + line_start);
-                }
+            let text = self.piece_text(piece);
+            if current_line + piece.line_count() >= line {
+                let local_start = if line == current_line {
+                    0
+                } else {
+                    piece.line_starts[line - current_line - 1]
+                };
+                return Some(char_offset + text[..local_start].chars().count());
             }
-            current_offset += piece.length;
+            current_line += piece.line_count();
+            char_offset += text.chars().count();
         }
 
-        None
+        Some(char_offset)
     }
 
-    /// Insert text at the specified offset
+    /// Check the internal invariants of the piece table
+    /// Verifies every piece's line-start cache and the cached length and line count
+    pub fn validate(&self) -> bool {
+        let mut length = 0;
+        let mut line_breaks = 0;
+
+        for piece in &self.pieces {
+            let buffer = match piece.buffer {
+                BufferType::Original => &self.original,
+                BufferType::Add => &self.add_buffer,
+            };
+            if !piece.validate_line_starts(buffer) {
+                return false;
+            }
+            length += piece.length;
+            line_breaks += piece.line_count();
+        }
+
+        length == self.total_length && line_breaks + 1 == self.line_count
+    }
+
+    /// Insert text at the specified byte offset
+    /// Nothing is inserted if the offset is past the end or inside a character
     pub fn insert(&mut self, offset: usize, text: &str) {
-        if text.is_empty() {
+        if text.is_empty() || !self.is_valid_offset(offset) {
             return;
         }
 
@@ -217,115 +629,227 @@ impl PieceTable {
         let new_lines = new_piece.line_count();
 
         if self.pieces.is_empty() {
-            self.pieces.push(new_piece);
+            self.pieces.push_back(new_piece);
         } else {
             self.insert_piece_at_offset(offset, new_piece);
         }
 
         self.total_length += text.len();
         self.line_count += new_lines;
+        self.version += 1;
+        self.invalidate_caches();
+        self.debug_check_line_count();
     }
 
-    /// Delete text at the specified offset with the given length
+    /// Delete `length` bytes at the specified byte offset
+    /// A range running past the end is clamped to the document; nothing is
+    /// deleted if either end falls inside a character
     pub fn delete(&mut self, offset: usize, length: usize) {
         if length == 0 || offset >= self.total_length {
             return;
         }
 
-        let deleted_text = self.get_text_range(offset, length);
-        let deleted_lines = deleted_text.matches('\n').count();
+        let length = length.min(self.total_length - offset);
+        if !self.is_valid_range(offset, length) {
+            return;
+        }
+        let deleted_lines: usize = self
+            .chunks_in_range(offset, length)
+            .map(|chunk| chunk.matches('\n').count())
+            .sum();
 
         self.delete_range(offset, length);
 
         self.total_length -= length;
-        self.line_count -= deleted_lines;
+        self.line_count = self.line_count.saturating_sub(deleted_lines).max(1);
+        self.version += 1;
+        self.invalidate_caches();
+        self.debug_check_line_count();
     }
 
-    /// Convert a character offset to a position (line, column)
-    pub fn offset_to_position(&self, offset: usize) -> Position {
-        if offset == 0 {
-            return Position::zero();
+    /// Drop the lazily built line index and text after a mutation
+    fn invalidate_caches(&mut self) {
+        self.line_index.take();
+        self.text_cache.take();
+    }
+
+    /// Assert that the cached line count matches a fresh recount in debug builds
+    /// Only small documents are recounted so large-document tests stay linear
+    fn debug_check_line_count(&self) {
+        if cfg!(debug_assertions) && self.total_length <= DEBUG_RECOUNT_LIMIT {
+            let recount = self.chunks().map(|chunk| chunk.matches('\n').count()).sum::<usize>() + 1;
+            debug_assert_eq!(self.line_count, recount, "cached line count drifted");
         }
+    }
 
-        let clamped_offset = offset.min(self.total_length);
-        let mut line = 0;
-        let mut last_line_start = 0;
-        let mut current_offset = 0;
+    /// Convert a byte offset to a position (line, byte column)
+    /// Offsets past the end are clamped and offsets inside a character snap to its start
+    pub fn offset_to_position(&self, offset: usize) -> Position {
+        let clamped_offset = self.floor_char_boundary(offset);
+        let line = self.line_at_offset(clamped_offset);
+        Position::new(line, clamped_offset - self.line_starts()[line])
+    }
 
-        for piece in &self.pieces {
-            for &line_start in &piece.line_starts {
-                let absolute_line_start = current_offset + line_start;
-                if absolute_line_start <= clamped_offset {
-                    line += 1;
-                    last_line_start = absolute_line_start;
-                } else {
-                    break;
-                }
+    /// Convert many byte offsets to positions with one ordered pass over the pieces
+    /// and line index; results are in input order and match `offset_to_position`
+    pub fn offsets_to_positions(&self, offsets: &[usize]) -> Vec<Position> {
+        let order = sorted_order(offsets);
+        let floored = self.floor_char_boundaries(offsets, &order);
+        let starts = self.line_starts();
+
+        let mut positions = vec![Position::zero(); offsets.len()];
+        let mut line = 0;
+        for index in order {
+            let offset = floored[index];
+            while line + 1 < starts.len() && starts[line + 1] <= offset {
+                line += 1;
             }
-            current_offset += piece.length;
-            if current_offset >= clamped_offset {
-                break;
+            positions[index] = Position::new(line, offset - starts[line]);
+        }
+        positions
+    }
+
+    /// Convert many positions to byte offsets in one ordered pass over the pieces
+    /// Results are in input order and match `position_to_offset`
+    pub fn positions_to_offsets(&self, positions: &[Position]) -> Vec<Option<usize>> {
+        // Columns are clamped to the line end, which is always a char boundary
+        let clamped: Vec<Option<usize>> = positions
+            .iter()
+            .map(|position| {
+                let line_offset = self.get_line_offset(position.line)?;
+                let length = self.line_byte_length(position.line)?;
+                Some(line_offset + position.column.min(length))
+            })
+            .collect();
+        let raw: Vec<usize> = clamped.iter().map(|offset| offset.unwrap_or(0)).collect();
+        let floored = self.floor_char_boundaries(&raw, &sorted_order(&raw));
+        clamped
+            .iter()
+            .zip(floored)
+            .map(|(offset, floored)| offset.map(|_| floored))
+            .collect()
+    }
+
+    /// Snap offsets back to char boundaries, visiting them in `order` so the
+    /// pieces are walked once; results are indexed like `offsets`
+    fn floor_char_boundaries(&self, offsets: &[usize], order: &[usize]) -> Vec<usize> {
+        let mut floored = vec![0; offsets.len()];
+        let mut pieces = self.pieces.iter().peekable();
+        let mut piece_start = 0;
+        for &index in order {
+            let offset = offsets[index].min(self.total_length);
+            while let Some(piece) = pieces.next_if(|piece| offset >= piece_start + piece.length) {
+                piece_start += piece.length;
             }
+            floored[index] = match pieces.peek() {
+                Some(piece) => {
+                    let text = self.piece_text(piece);
+                    let mut local = offset - piece_start;
+                    while !text.is_char_boundary(local) {
+                        local -= 1;
+                    }
+                    piece_start + local
+                }
+                None => self.total_length,
+            };
         }
+        floored
+    }
 
-        Position::new(line, clamped_offset - last_line_start)
+    /// Get the line containing an offset, clamped to the document length
+    pub fn line_at_offset(&self, offset: usize) -> usize {
+        let clamped_offset = offset.min(self.total_length);
+        self.line_starts()
+            .partition_point(|&start| start <= clamped_offset)
+            - 1
     }
 
-    /// Convert a position (line, column) to a character offset
+    /// Convert a position (line, byte column) to a byte offset
+    /// Columns past the line end are clamped and columns inside a character snap to its start
     pub fn position_to_offset(&self, line: usize, column: usize) -> Option<usize> {
         let line_offset = self.get_line_offset(line)?;
         let line_text = self.get_line(line)?;
-        let max_column = line_text.len();
-        let clamped_column = column.min(max_column);
+        let mut clamped_column = column.min(line_text.len());
+        while !line_text.is_char_boundary(clamped_column) {
+            clamped_column -= 1;
+        }
         Some(line_offset + clamped_column)
     }
 
+    /// Insert a piece at a byte offset, splitting the piece it falls inside
+    /// Boundary rule: an insertion between two pieces always joins the piece on
+    /// its left, extending it when the new text continues it in the same buffer
+    /// and otherwise going directly after it. Only offset 0 has no left piece.
     fn insert_piece_at_offset(&mut self, offset: usize, new_piece: Piece) {
-        if offset == 0 {
-            self.pieces.insert(0, new_piece);
+        let Some((index, local)) = self.locate_left(offset) else {
+            self.pieces.push_front(new_piece);
             return;
-        }
+        };
 
-        if offset >= self.total_length {
-            self.pieces.push(new_piece);
-            return;
+        let piece = &self.pieces[index];
+        if local < piece.length {
+            let left = self.split_piece(piece, 0, local);
+            let right = self.split_piece(piece, local, piece.length - local);
+            self.pieces[index] = left;
+            self.pieces.insert(index + 1, new_piece);
+            self.pieces.insert(index + 2, right);
+        } else if !self.extend_piece(index, &new_piece) {
+            self.pieces.insert(index + 1, new_piece);
         }
+    }
 
+    /// Find the piece an offset falls in, mapping a boundary to the end of the
+    /// piece before it; offsets past the end map to the end of the last piece
+    /// Returns (piece index, offset within the piece), or None at offset 0
+    fn locate_left(&self, offset: usize) -> Option<(usize, usize)> {
+        if offset == 0 {
+            return None;
+        }
         let mut current_offset = 0;
-        let mut insert_index = self.pieces.len();
-
         for (i, piece) in self.pieces.iter().enumerate() {
-            let piece_end = current_offset + piece.length;
-
-            if offset == current_offset {
-                insert_index = i;
-                break;
+            if offset <= current_offset + piece.length {
+                return Some((i, offset - current_offset));
             }
+            current_offset += piece.length;
+        }
+        let last = self.pieces.len().checked_sub(1)?;
+        Some((last, self.pieces[last].length))
+    }
 
-            if offset > current_offset && offset < piece_end {
-                // Split the piece
-                let split_point = offset - current_offset;
-                let left = self.split_piece(piece, 0, split_point);
-                let right = self.split_piece(piece, split_point, piece.length - split_point);
-
-                self.pieces.splice(i..=i, vec![left, new_piece, right]);
-                return;
-            }
-
-            if offset == piece_end {
-                insert_index = i + 1;
-                break;
+    /// Get the cached absolute line starts, building them with one pass over the pieces
+    fn line_starts(&self) -> &[usize] {
+        self.line_index.get_or_init(|| {
+            let mut starts = Vec::with_capacity(self.line_count);
+            starts.push(0);
+            let mut current_offset = 0;
+            for piece in &self.pieces {
+                starts.extend(piece.line_starts.iter().map(|&start| current_offset + start));
+                current_offset += piece.length;
             }
+            starts
+        })
+    }
 
-            current_offset = piece_end;
+    /// Grow a piece in place when `new_piece` continues it in the same buffer
+    /// This keeps sequential typing from creating one piece per keystroke
+    fn extend_piece(&mut self, index: usize, new_piece: &Piece) -> bool {
+        let piece = &mut self.pieces[index];
+        if piece.buffer != new_piece.buffer || piece.start + piece.length != new_piece.start
+        {
+            return false;
         }
 
-        self.pieces.insert(insert_index, new_piece);
+        let base = piece.length;
+        piece
+            .line_starts
+            .extend(new_piece.line_starts.iter().map(|&start| base + start));
+        piece.length += new_piece.length;
+        true
     }
 
     fn delete_range(&mut self, offset: usize, length: usize) {
         let end_offset = offset + length;
-        let mut new_pieces = Vec::new();
+        let mut new_pieces = VecDeque::with_capacity(self.pieces.len() + 1);
         let mut current_offset = 0;
 
         for piece in &self.pieces {
@@ -334,7 +858,7 @@ impl PieceTable {
 
             if piece_end <= offset || piece_start >= end_offset {
                 // Piece is completely outside the delete range
-                new_pieces.push(piece.clone());
+                new_pieces.push_back(piece.clone());
             } else if piece_start >= offset && piece_end <= end_offset {
                 // Piece is completely inside the delete range - skip it
             } else if piece_start < offset && piece_end > end_offset {
@@ -343,17 +867,17 @@ impl PieceTable {
                 let right_start = end_offset - piece_start;
                 let right_len = piece_end - end_offset;
 
-                new_pieces.push(self.split_piece(piece, 0, left_len));
-                new_pieces.push(self.split_piece(piece, right_start, right_len));
+                new_pieces.push_back(self.split_piece(piece, 0, left_len));
+                new_pieces.push_back(self.split_piece(piece, right_start, right_len));
             } else if piece_start < offset {
                 // Delete range starts in this piece
                 let keep_len = offset - piece_start;
-                new_pieces.push(self.split_piece(piece, 0, keep_len));
+                new_pieces.push_back(self.split_piece(piece, 0, keep_len));
             } else {
                 // Delete range ends in this piece
                 let skip_len = end_offset - piece_start;
                 let keep_len = piece.length - skip_len;
-                new_pieces.push(self.split_piece(piece, skip_len, keep_len));
+                new_pieces.push_back(self.split_piece(piece, skip_len, keep_len));
             }
 
             current_offset = piece_end;
@@ -362,6 +886,26 @@ impl PieceTable {
         self.pieces = new_pieces;
     }
 
+    /// Find the index and starting offset of the piece containing an offset
+    fn find_piece(&self, offset: usize) -> Option<(usize, usize)> {
+        let mut current_offset = 0;
+        for (i, piece) in self.pieces.iter().enumerate() {
+            if offset < current_offset + piece.length {
+                return Some((i, current_offset));
+            }
+            current_offset += piece.length;
+        }
+        None
+    }
+
+    fn piece_text(&self, piece: &Piece) -> &str {
+        let buffer = match piece.buffer {
+            BufferType::Original => &self.original,
+            BufferType::Add => &self.add_buffer,
+        };
+        &buffer[piece.start..piece.start + piece.length]
+    }
+
     fn split_piece(&self, piece: &Piece, offset: usize, length: usize) -> Piece {
         let buffer = match piece.buffer {
             BufferType::Original => &self.original,
@@ -373,6 +917,13 @@ impl PieceTable {
     }
 }
 
+/// Indices of `values` in ascending order of value
+fn sorted_order(values: &[usize]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by_key(|&index| values[index]);
+    order
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -451,6 +1002,412 @@ mod tests {
         assert_eq!(pt.offset_to_position(6), Position::new(2, 0));
     }
 
+    #[test]
+    fn test_to_chunks() {
+        let mut pt = PieceTable::new("h\u{e9}llo w\u{f6}rld ".to_string());
+        pt.insert(13, "\u{1f600} done");
+
+        let chunks = pt.to_chunks(4);
+        assert!(chunks.iter().all(|c| c.len() <= 4));
+        assert_eq!(chunks.concat(), pt.get_text());
+
+        let tiny = pt.to_chunks(1);
+        assert!(tiny.contains(&"\u{1f600}".to_string()));
+        assert_eq!(tiny.concat(), pt.get_text());
+    }
+
+    #[test]
+    fn test_line_at_offset() {
+        let mut pt = PieceTable::new("ab\ncd\nef".to_string());
+        pt.insert(4, "x\ny\n");
+        for offset in 0..=pt.get_length() + 2 {
+            assert_eq!(pt.line_at_offset(offset), pt.offset_to_position(offset).line);
+        }
+    }
+
+    #[test]
+    fn test_prepend_many_lines() {
+        let mut pt = PieceTable::new(String::new());
+        for i in 0..10_000 {
+            pt.insert(0, &format!("line {}\n", i));
+        }
+
+        assert_eq!(pt.get_line_count(), 10_001);
+        assert_eq!(pt.get_line(0), Some("line 9999".to_string()));
+        assert_eq!(pt.get_line(9_999), Some("line 0".to_string()));
+    }
+
+    #[test]
+    fn test_char_offset_of_line() {
+        let mut pt = PieceTable::new("caf\u{e9}\n\u{1f600}x\nend".to_string());
+        pt.insert(0, "\u{e9}\n");
+        assert_eq!(pt.char_offset_of_line(0), Some(0));
+        assert_eq!(pt.char_offset_of_line(1), Some(2));
+        assert_eq!(pt.get_line_offset(1), Some(3));
+        assert_eq!(pt.char_offset_of_line(2), Some(7));
+        assert_eq!(pt.char_offset_of_line(3), Some(10));
+        assert_eq!(pt.get_line_offset(3), Some(15));
+        assert_eq!(pt.char_offset_of_line(4), None);
+    }
+
+    #[test]
+    fn test_validate_detects_corrupted_line_starts() {
+        let mut pt = PieceTable::new("ab\ncd".to_string());
+        pt.insert(2, "x\ny");
+        assert!(pt.validate());
+
+        pt.pieces[1].line_starts[0] += 1;
+        assert!(!pt.validate());
+        assert!(!pt.pieces[1].validate_line_starts(&pt.add_buffer));
+        assert!(pt.pieces[0].validate_line_starts(&pt.original));
+    }
+
+    #[test]
+    fn test_utf16_to_offset() {
+        let mut pt = PieceTable::new("a\u{1f600}".to_string());
+        pt.insert(5, "\u{e9}b");
+        assert_eq!(pt.utf16_to_offset(0), Some(0));
+        assert_eq!(pt.utf16_to_offset(1), Some(1));
+        assert_eq!(pt.utf16_to_offset(2), None);
+        assert_eq!(pt.utf16_to_offset(3), Some(5));
+        assert_eq!(pt.utf16_to_offset(4), Some(7));
+        assert_eq!(pt.utf16_to_offset(5), Some(8));
+        assert_eq!(pt.utf16_to_offset(6), None);
+    }
+
+    #[test]
+    fn test_snapshot_survives_edits() {
+        let mut pt = PieceTable::new("hello".to_string());
+        pt.insert(5, " world");
+        let snapshot = pt.snapshot();
+
+        pt.delete(0, 6);
+        pt.insert(0, "goodbye ");
+        assert_eq!(pt.get_text(), "goodbye world");
+        assert_eq!(pt.snapshot_text(&snapshot), "hello world");
+    }
+
+    #[test]
+    fn test_line_lengths_matches_per_line() {
+        let mut pt = PieceTable::new(String::new());
+        for i in 0..200 {
+            let offset = if i % 3 == 0 { 0 } else { pt.get_length() };
+            pt.insert(offset, &format!("l\u{ed}ne {}\n", "x".repeat(i % 17)));
+        }
+
+        let lengths = pt.line_lengths();
+        assert_eq!(lengths.len(), pt.get_line_count());
+        for (line, &length) in lengths.iter().enumerate() {
+            assert_eq!(length, pt.get_line(line).unwrap().chars().count());
+            assert_eq!(Some(length), pt.line_char_length(line));
+        }
+    }
+
+    #[test]
+    fn test_chunks_in_range() {
+        let mut pt = PieceTable::new("hello world".to_string());
+        pt.insert(5, ",");
+        let chunks: Vec<&str> = pt.chunks_in_range(3, 6).collect();
+        assert_eq!(chunks, vec!["lo", ",", " wo"]);
+        assert_eq!(pt.chunks_in_range(8, 100).collect::<String>(), "orld");
+        assert_eq!(pt.chunks_in_range(50, 5).count(), 0);
+    }
+
+    #[test]
+    fn test_offset_to_utf16() {
+        let mut pt = PieceTable::new("a\u{1f600}".to_string());
+        pt.insert(5, "\u{e9}b");
+        assert_eq!(pt.utf16_length(), 5);
+        assert_eq!(pt.offset_to_utf16(1), 1);
+        assert_eq!(pt.offset_to_utf16(3), 1);
+        assert_eq!(pt.offset_to_utf16(5), 3);
+        assert_eq!(pt.offset_to_utf16(8), 5);
+        assert_eq!(pt.offset_to_utf16(99), 5);
+    }
+
+    #[test]
+    fn test_sequential_typing_coalesces_pieces() {
+        let mut pt = PieceTable::new(String::new());
+        for i in 0..1000 {
+            pt.insert(i, if i % 100 == 99 { "\n" } else { "x" });
+        }
+        assert_eq!(pt.piece_count(), 1);
+        assert_eq!(pt.get_length(), 1000);
+        assert_eq!(pt.get_line_count(), 11);
+        assert!(pt.validate());
+
+        let mut pt = PieceTable::new("hello world".to_string());
+        for (i, c) in ",\nthere".chars().enumerate() {
+            pt.insert(5 + i, &c.to_string());
+        }
+        assert_eq!(pt.get_text(), "hello,\nthere world");
+        assert_eq!(pt.piece_count(), 3);
+        assert!(pt.validate());
+    }
+
+    #[test]
+    fn test_insert_after_delete_starts_new_piece() {
+        let mut pt = PieceTable::new(String::new());
+        pt.insert(0, "abc");
+        pt.delete(2, 1);
+        pt.insert(2, "d");
+        assert_eq!(pt.get_text(), "abd");
+        assert_eq!(pt.piece_count(), 2);
+        assert!(pt.validate());
+    }
+
+    #[test]
+    fn test_line_index_lookups_on_large_document() {
+        let mut pt = PieceTable::new("line\n".repeat(50_000));
+        for _ in 0..50_000 {
+            pt.insert(0, "line\n");
+        }
+        assert_eq!(pt.get_line_count(), 100_001);
+        assert!(pt.line_index.get().is_none());
+
+        for line in 0..100_000 {
+            assert_eq!(pt.get_line_offset(line), Some(line * 5));
+        }
+        assert_eq!(pt.get_line_offset(100_000), Some(500_000));
+        assert_eq!(pt.get_line_offset(100_001), None);
+        assert_eq!(pt.offset_to_position(499_999), Position::new(99_999, 4));
+        assert!(pt.line_index.get().is_some());
+
+        pt.delete(0, 5);
+        assert!(pt.line_index.get().is_none());
+        assert_eq!(pt.get_line_offset(99_999), Some(499_995));
+    }
+
+    #[test]
+    fn test_text_cache_invalidated_by_edits() {
+        let mut pt = PieceTable::new("hello".to_string());
+        assert!(pt.text_cache.get().is_none());
+        assert_eq!(pt.get_text(), "hello");
+        assert!(pt.text_cache.get().is_some());
+        assert_eq!(pt.get_text(), "hello");
+
+        pt.insert(5, " world");
+        assert!(pt.text_cache.get().is_none());
+        assert_eq!(pt.get_text(), "hello world");
+        pt.delete(0, 6);
+        assert_eq!(pt.get_text(), "world");
+        pt.insert(0, "a ");
+        pt.insert(2, "new ");
+        assert_eq!(pt.text(), "a new world");
+
+        pt.compact();
+        assert!(pt.text_cache.get().is_none());
+        assert_eq!(pt.get_text(), "a new world");
+
+        pt.reset("fresh".to_string());
+        assert_eq!(pt.get_text(), "fresh");
+    }
+
+    #[test]
+    fn test_line_lengths_match_get_line() {
+        let mut pt = PieceTable::new("first\nsec".to_string());
+        pt.insert(9, "ond caf\u{e9}\n");
+        pt.insert(0, "\n");
+        pt.insert(pt.get_length(), "tail");
+        assert!(pt.piece_count() > 1);
+
+        for line in 0..pt.get_line_count() {
+            let text = pt.get_line(line).unwrap();
+            assert_eq!(pt.line_byte_length(line), Some(text.len()));
+            assert_eq!(pt.line_char_length(line), Some(text.chars().count()));
+        }
+        assert_eq!(pt.line_char_length(2), Some(11));
+        assert_eq!(pt.line_byte_length(2), Some(12));
+        assert_eq!(pt.line_byte_length(pt.get_line_count()), None);
+        assert_eq!(pt.line_char_length(pt.get_line_count()), None);
+    }
+
+    #[test]
+    fn test_append_original_matches_new() {
+        let text = "alpha\nbeta\n\ngam\u{e9}a\ndelta";
+        let mut pt = PieceTable::new(String::new());
+        for chunk in ["alp", "ha\nbe", "ta\n", "\n", "gam\u{e9}a\nd", "", "elta"] {
+            pt.append_original(chunk);
+        }
+        let whole = PieceTable::new(text.to_string());
+
+        assert_eq!(pt.get_text(), text);
+        assert_eq!(pt.piece_count(), 1);
+        assert_eq!(pt.get_line_count(), whole.get_line_count());
+        assert_eq!(pt.pieces[0].line_starts, whole.pieces[0].line_starts);
+        assert!(pt.validate());
+
+        pt.insert(3, "x");
+        pt.append_original("!");
+        assert_eq!(pt.get_text(), "alpxha\nbeta\n\ngam\u{e9}a\ndelta!");
+        assert!(pt.validate());
+    }
+
+    fn layout(pt: &PieceTable) -> Vec<(BufferType, usize, usize)> {
+        pt.pieces.iter().map(|piece| (piece.buffer, piece.start, piece.length)).collect()
+    }
+
+    #[test]
+    fn test_boundary_insert_joins_left_piece() {
+        let mut pt = PieceTable::new("abcd".to_string());
+        pt.insert(2, "X");
+        assert_eq!(pt.locate_left(2), Some((0, 2)));
+        assert_eq!(pt.locate_left(3), Some((1, 1)));
+
+        // Continues the add buffer right after "X", so it extends that piece
+        pt.insert(3, "Y");
+        assert_eq!(
+            layout(&pt),
+            vec![(BufferType::Original, 0, 2), (BufferType::Add, 0, 2), (BufferType::Original, 2, 2)]
+        );
+
+        // Doesn't continue "ab", so it goes directly after it
+        pt.insert(2, "Z");
+        assert_eq!(
+            layout(&pt),
+            vec![
+                (BufferType::Original, 0, 2),
+                (BufferType::Add, 2, 1),
+                (BufferType::Add, 0, 2),
+                (BufferType::Original, 2, 2),
+            ]
+        );
+        assert_eq!(pt.get_text(), "abZXYcd");
+        assert!(pt.validate());
+    }
+
+    #[test]
+    fn test_boundary_insert_same_from_split_or_existing_boundary() {
+        // The boundary at offset 2 already exists in one table and is created by a split in the other
+        let mut split = PieceTable::new("abcd".to_string());
+        split.insert(2, "Q");
+        let mut existing = PieceTable::new("abcd".to_string());
+        let whole = existing.pieces[0].clone();
+        existing.pieces = VecDeque::from(vec![existing.split_piece(&whole, 0, 2), existing.split_piece(&whole, 2, 2)]);
+        existing.insert(2, "Q");
+        assert_eq!(layout(&split), layout(&existing));
+
+        split.insert(0, "S");
+        split.insert(split.get_length(), "E");
+        assert_eq!(split.get_text(), "SabQcdE");
+        assert!(split.validate());
+    }
+
+    #[test]
+    fn test_compact_reclaims_deleted_inserts() {
+        let mut pt = PieceTable::new("head\n".to_string());
+        pt.insert(5, &"x".repeat(10_000));
+        pt.insert(0, "top ");
+        pt.delete(9, 9_990);
+        let text = pt.get_text();
+        assert_eq!(pt.add_buffer.len(), 10_004);
+
+        assert_eq!(pt.compact(), 9_990);
+        assert_eq!(pt.add_buffer.len(), 14);
+        assert_eq!(pt.get_text(), text);
+        assert!(pt.validate());
+
+        pt.insert(pt.get_length(), "y");
+        assert_eq!(pt.get_text(), format!("{}y", text));
+        assert!(pt.validate());
+    }
+
+    #[test]
+    fn test_get_lines_matches_get_line() {
+        let mut pt = PieceTable::new("one\ntwo\nthree".to_string());
+        pt.insert(4, "inserted\nmid");
+        pt.insert(0, "zero\n");
+        pt.insert(pt.get_length(), "\n");
+        assert!(pt.piece_count() > 3);
+
+        let count = pt.get_line_count();
+        for start in 0..count {
+            for end in start..count + 2 {
+                let expected: Vec<String> = (start..end.min(count)).filter_map(|line| pt.get_line(line)).collect();
+                assert_eq!(pt.get_lines(start, end), expected);
+            }
+        }
+        assert!(pt.get_lines(3, 1).is_empty());
+    }
+
+    fn recount(pt: &PieceTable) -> usize {
+        pt.get_text().matches('\n').count() + 1
+    }
+
+    #[test]
+    fn test_line_count_after_deletes() {
+        let mut pt = PieceTable::new("a\nb\nc\nd\ne".to_string());
+        pt.insert(4, "x\ny\n");
+        pt.delete(1, 7);
+        assert_eq!(pt.get_line_count(), recount(&pt));
+
+        // Up to but not including a newline
+        let newline = pt.get_text().find('\n').unwrap();
+        pt.delete(0, newline);
+        assert_eq!(pt.get_line_count(), recount(&pt));
+
+        pt.delete(0, pt.get_length());
+        assert_eq!(pt.get_text(), "");
+        assert_eq!(pt.get_line_count(), 1);
+    }
+
+    #[test]
+    fn test_delete_past_end_is_clamped() {
+        let mut pt = PieceTable::new("ab\ncd".to_string());
+        pt.delete(1, 100);
+        assert_eq!(pt.get_text(), "a");
+        assert_eq!(pt.get_length(), 1);
+        assert_eq!(pt.get_line_count(), 1);
+
+        let mut empty = PieceTable::new(String::new());
+        empty.delete(0, 5);
+        empty.insert(0, "\n\n");
+        assert_eq!(empty.get_line_count(), 3);
+        assert!(empty.validate());
+    }
+
+    #[test]
+    fn test_bad_offsets_are_rejected() {
+        let mut pt = PieceTable::new("a\u{1f600}b".to_string());
+        pt.insert(2, "x");
+        pt.insert(99, "x");
+        pt.delete(3, 1);
+        pt.delete(0, 2);
+        assert_eq!(pt.get_text(), "a\u{1f600}b");
+
+        assert_eq!(pt.get_text_range(1, usize::MAX), "\u{1f600}b");
+        assert_eq!(pt.get_text_range(usize::MAX, usize::MAX), "");
+        assert_eq!(pt.get_text_range(0, 3), "a");
+        pt.delete(1, usize::MAX);
+        assert_eq!(pt.get_text(), "a");
+    }
+
+    #[test]
+    fn test_batch_conversions_match_single() {
+        let mut pt = PieceTable::new("first\nse\u{e9}cond\n".to_string());
+        pt.insert(9, "\u{1f600}x\n");
+        pt.insert(0, "top\n");
+        let offsets = [30, 0, 14, 7, 99, 3, 15, 4, 22, 13, 14, 1];
+        let expected: Vec<Position> = offsets.iter().map(|&offset| pt.offset_to_position(offset)).collect();
+        assert_eq!(pt.offsets_to_positions(&offsets), expected);
+
+        let positions = [
+            Position::new(2, 5),
+            Position::new(0, 9),
+            Position::new(2, 3),
+            Position::new(5, 0),
+            Position::new(1, 0),
+            Position::new(2, 7),
+            Position::new(4, 0),
+        ];
+        let expected: Vec<Option<usize>> = positions
+            .iter()
+            .map(|position| pt.position_to_offset(position.line, position.column))
+            .collect();
+        assert_eq!(pt.positions_to_offsets(&positions), expected);
+        assert!(pt.offsets_to_positions(&[]).is_empty());
+    }
+
     #[test]
     fn test_position_to_offset() {
         let pt = PieceTable::new("ab\ncd\nef".to_string());
@@ -1,11 +1,12 @@
 use serde::{Deserialize, Serialize};
 
 /// Represents a position in the document (line and column)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Positions order by line, then by column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Position {
     /// Line number (0-indexed)
     pub line: usize,
-    /// Column number (0-indexed)
+    /// Column as a byte offset from the line start (0-indexed)
     pub column: usize,
 }
 
@@ -26,6 +27,8 @@ impl Default for Position {
 }
 
 /// Represents a range in the document
+/// The range is half-open: it covers `start` up to but not including `end`, so an
+/// empty range contains no position, not even its own start
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Range {
     /// Start position (inclusive)
@@ -51,17 +54,61 @@ impl Range {
         self.start == self.end
     }
 
+    /// Get the range with its ends ordered so `start <= end`
+    /// A reversed range, such as a selection made backwards, covers the same text
+    pub fn normalized(&self) -> Self {
+        if self.start <= self.end {
+            *self
+        } else {
+            Range::new(self.end, self.start)
+        }
+    }
+
     /// Check if this range contains a position
+    /// Reversed ranges are normalized first; the end position is never contained
     pub fn contains(&self, position: Position) -> bool {
-        if position.line < self.start.line || position.line > self.end.line {
-            return false;
-        }
-        if position.line == self.start.line && position.column < self.start.column {
-            return false;
-        }
-        if position.line == self.end.line && position.column >= self.end.column {
-            return false;
-        }
-        true
+        let range = self.normalized();
+        range.start <= position && position < range.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_multiline_range() {
+        let range = Range::from_positions(1, 4, 3, 2);
+        assert!(range.contains(Position::new(1, 4)));
+        assert!(range.contains(Position::new(2, 0)));
+        assert!(range.contains(Position::new(2, 99)));
+        assert!(range.contains(Position::new(3, 1)));
+        assert!(!range.contains(Position::new(1, 3)));
+        assert!(!range.contains(Position::new(3, 2)));
+    }
+
+    #[test]
+    fn test_contains_reversed_range() {
+        let range = Range::from_positions(3, 2, 1, 4);
+        assert_eq!(range.normalized(), Range::from_positions(1, 4, 3, 2));
+        assert!(range.contains(Position::new(1, 4)));
+        assert!(range.contains(Position::new(2, 0)));
+        assert!(!range.contains(Position::new(3, 2)));
+        assert!(!range.contains(Position::new(0, 0)));
+    }
+
+    #[test]
+    fn test_zero_width_range_contains_nothing() {
+        let range = Range::from_positions(2, 5, 2, 5);
+        assert!(range.is_empty());
+        assert!(!range.contains(Position::new(2, 5)));
+        assert!(!range.contains(Position::new(2, 4)));
+    }
+
+    #[test]
+    fn test_exclusive_end_on_same_line() {
+        let range = Range::from_positions(0, 1, 0, 3);
+        assert!(range.contains(Position::new(0, 2)));
+        assert!(!range.contains(Position::new(0, 3)));
     }
 }
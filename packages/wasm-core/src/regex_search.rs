@@ -0,0 +1,104 @@
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::position::Range;
+
+/// Why a regex search could not run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RegexErrorKind {
+    /// The pattern failed to compile
+    InvalidPattern,
+    /// The flags string contained an unsupported flag
+    InvalidFlag,
+}
+
+/// Error returned to JS as `{ kind, message }` instead of panicking
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RegexError {
+    pub kind: RegexErrorKind,
+    pub message: String,
+}
+
+/// A regex match as byte offsets, with one entry per capture group
+/// Groups that didn't participate in the match are None
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexMatch {
+    pub start: usize,
+    pub end: usize,
+    pub groups: Vec<Option<(usize, usize)>>,
+}
+
+/// A regex match converted to document positions, serialized as `{ range, groups }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RegexMatchRange {
+    pub range: Range,
+    pub groups: Vec<Option<Range>>,
+}
+
+/// Compile a pattern with JS-style flags
+/// Supported flags are `i` (case-insensitive), `m` (multi-line anchors),
+/// `s` (dot matches newline) and `x` (ignore whitespace)
+pub fn compile(pattern: &str, flags: &str) -> Result<Regex, RegexError> {
+    let mut builder = RegexBuilder::new(pattern);
+    for flag in flags.chars() {
+        match flag {
+            'i' => builder.case_insensitive(true),
+            'm' => builder.multi_line(true),
+            's' => builder.dot_matches_new_line(true),
+            'x' => builder.ignore_whitespace(true),
+            _ => {
+                return Err(RegexError {
+                    kind: RegexErrorKind::InvalidFlag,
+                    message: format!("unsupported regex flag '{}'", flag),
+                })
+            }
+        };
+    }
+
+    builder.build().map_err(|error| RegexError {
+        kind: RegexErrorKind::InvalidPattern,
+        message: error.to_string(),
+    })
+}
+
+/// Find all non-overlapping matches of `regex` in `text`, in document order
+pub fn find_all(regex: &Regex, text: &str) -> Vec<RegexMatch> {
+    regex
+        .captures_iter(text)
+        .map(|captures| {
+            let whole = captures.get(0).expect("group 0 always participates");
+            RegexMatch {
+                start: whole.start(),
+                end: whole.end(),
+                groups: captures
+                    .iter()
+                    .skip(1)
+                    .map(|group| group.map(|m| (m.start(), m.end())))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_all_with_groups() {
+        let regex = compile(r"(\w+)@(\w+)?", "").unwrap();
+        let matches = find_all(&regex, "a@b c@");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].groups, vec![Some((0, 1)), Some((2, 3))]);
+        assert_eq!((matches[1].start, matches[1].end), (4, 6));
+        assert_eq!(matches[1].groups, vec![Some((4, 5)), None]);
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        assert_eq!(compile("(", "").unwrap_err().kind, RegexErrorKind::InvalidPattern);
+        assert_eq!(compile("a", "g").unwrap_err().kind, RegexErrorKind::InvalidFlag);
+        assert!(compile("^a$", "im").unwrap().is_match("x\nA"));
+    }
+}
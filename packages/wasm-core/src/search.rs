@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+/// Options controlling how a query matches, passed from JS as
+/// `{ caseSensitive, wholeWord }`; missing fields default to false
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+/// Check whether a character is part of a word for whole-word matching
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Try to match `query` at byte offset `start` of `text`
+/// Returns the end offset of the match
+fn match_at(text: &str, start: usize, query: &str, case_sensitive: bool) -> Option<usize> {
+    if case_sensitive {
+        return text[start..].starts_with(query).then_some(start + query.len());
+    }
+
+    let mut candidate = text[start..].char_indices();
+    for q in query.chars() {
+        let (_, c) = candidate.next()?;
+        if c != q && !c.to_lowercase().eq(q.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(candidate.next().map_or(text.len(), |(i, _)| start + i))
+}
+
+/// Check that a match isn't directly preceded or followed by word characters
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+/// Find the first match of `query` starting at or after byte offset `start`
+/// Returns (start, end) byte offsets, or None if there is no further match
+pub fn find_next(text: &str, query: &str, start: usize, options: SearchOptions) -> Option<(usize, usize)> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let mut offset = start;
+    while offset < text.len() {
+        match match_at(text, offset, query, options.case_sensitive) {
+            Some(end) if !options.whole_word || is_whole_word(text, offset, end) => {
+                return Some((offset, end));
+            }
+            _ => {
+                offset += text[offset..].chars().next().map_or(1, |c| c.len_utf8());
+            }
+        }
+    }
+
+    None
+}
+
+/// Find the last match of `query` that ends at or before byte offset `end`
+/// A match ending exactly at `end` counts. Returns (start, end) byte offsets
+pub fn find_prev(text: &str, query: &str, end: usize, options: SearchOptions) -> Option<(usize, usize)> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let end = end.min(text.len());
+    text[..end].char_indices().rev().find_map(|(offset, _)| {
+        match_at(text, offset, query, options.case_sensitive)
+            .filter(|&match_end| match_end <= end)
+            .filter(|&match_end| !options.whole_word || is_whole_word(text, offset, match_end))
+            .map(|match_end| (offset, match_end))
+    })
+}
+
+/// Find all non-overlapping matches of `query` in `text`
+/// Returns (start, end) byte offsets in document order
+pub fn find_all(text: &str, query: &str, options: SearchOptions) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    let mut offset = 0;
+    while let Some((start, end)) = find_next(text, query, offset, options) {
+        matches.push((start, end));
+        offset = end;
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXACT: SearchOptions = SearchOptions { case_sensitive: true, whole_word: false };
+    const WHOLE_WORD: SearchOptions = SearchOptions { case_sensitive: true, whole_word: true };
+
+    #[test]
+    fn test_find_all_case_sensitivity() {
+        assert_eq!(find_all("Foo foo FOO", "foo", EXACT), vec![(4, 7)]);
+        assert_eq!(find_all("Foo foo FOO", "foo", SearchOptions::default()), vec![(0, 3), (4, 7), (8, 11)]);
+    }
+
+    #[test]
+    fn test_find_all_whole_word() {
+        let text = "id width id_x (id)";
+        assert_eq!(find_all(text, "id", EXACT).len(), 4);
+        assert_eq!(find_all(text, "id", WHOLE_WORD), vec![(0, 2), (15, 17)]);
+    }
+
+    #[test]
+    fn test_find_all_non_overlapping() {
+        assert_eq!(find_all("aaaa", "aa", EXACT), vec![(0, 2), (2, 4)]);
+        assert_eq!(find_all("caf\u{e9} CAF\u{c9}", "caf\u{e9}", SearchOptions::default()), vec![(0, 5), (6, 11)]);
+    }
+
+    #[test]
+    fn test_find_prev_before_offset() {
+        let text = "ab ab ab";
+        assert_eq!(find_prev(text, "ab", 8, EXACT), Some((6, 8)));
+        assert_eq!(find_prev(text, "ab", 7, EXACT), Some((3, 5)));
+        assert_eq!(find_prev(text, "ab", 5, EXACT), Some((3, 5)));
+        assert_eq!(find_prev(text, "ab", 4, EXACT), Some((0, 2)));
+        assert_eq!(find_prev(text, "ab", 1, EXACT), None);
+        assert_eq!(find_prev(text, "ab", 99, EXACT), Some((6, 8)));
+        assert_eq!(find_prev("xab abc", "ab", 7, WHOLE_WORD), None);
+        assert_eq!(find_prev("Caf\u{e9} caf\u{c9}", "CAF\u{c9}", 12, SearchOptions::default()), Some((6, 11)));
+    }
+
+    #[test]
+    fn test_find_next_from_offset() {
+        assert_eq!(find_next("aaa", "aa", 0, EXACT), Some((0, 2)));
+        assert_eq!(find_next("aaa", "aa", 1, EXACT), Some((1, 3)));
+        assert_eq!(find_next("aaa", "aa", 2, EXACT), None);
+        assert_eq!(find_next("ab ab", "ab", 1, WHOLE_WORD), Some((3, 5)));
+        assert_eq!(find_next("abc", "", 0, EXACT), None);
+    }
+}
@@ -0,0 +1,127 @@
+use wasm_bindgen::prelude::*;
+
+use crate::search::{self, SearchOptions};
+use crate::{Document, Range};
+
+/// Yields the matches of a query one at a time or in batches
+///
+/// The cursor remembers where the last match ended, so the host can pull
+/// results as needed instead of receiving every match at once. Like
+/// `CharCursor`, the document is passed to each call and any edit invalidates
+/// the cursor: afterwards `next` returns null and `nextBatch` an empty array.
+#[wasm_bindgen]
+pub struct SearchCursor {
+    query: String,
+    options: SearchOptions,
+    version: u64,
+    offset: usize,
+}
+
+impl SearchCursor {
+    pub(crate) fn new(document: &Document, query: &str, options: SearchOptions) -> SearchCursor {
+        SearchCursor {
+            query: query.to_string(),
+            options,
+            version: document.piece_table.version(),
+            offset: 0,
+        }
+    }
+
+    /// Advance to the next match, or None when exhausted or invalidated
+    pub(crate) fn next_range(&mut self, document: &Document) -> Option<Range> {
+        if !self.is_valid(document) {
+            return None;
+        }
+        let text = document.piece_table.text();
+        let (start, end) = search::find_next(text, &self.query, self.offset, self.options)?;
+        self.offset = end;
+        Some(document.byte_range(start, end))
+    }
+
+    /// Advance over up to `count` matches
+    pub(crate) fn next_ranges(&mut self, document: &Document, count: usize) -> Vec<Range> {
+        std::iter::from_fn(|| self.next_range(document)).take(count).collect()
+    }
+}
+
+#[wasm_bindgen]
+impl SearchCursor {
+    /// Get the next match as a position range, or null when there are no more
+    #[wasm_bindgen]
+    pub fn next(&mut self, document: &Document) -> JsValue {
+        match self.next_range(document) {
+            Some(range) => serde_wasm_bindgen::to_value(&range).unwrap_or(JsValue::NULL),
+            None => JsValue::NULL,
+        }
+    }
+
+    /// Get up to `count` further matches as an array of position ranges
+    /// A shorter array means the search is exhausted
+    #[wasm_bindgen(js_name = nextBatch)]
+    pub fn next_batch(&mut self, document: &Document, count: usize) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.next_ranges(document, count)).unwrap_or(JsValue::NULL)
+    }
+
+    /// Check that the document hasn't been edited since the cursor was created
+    #[wasm_bindgen(js_name = isValid)]
+    pub fn is_valid(&self, document: &Document) -> bool {
+        self.version == document.piece_table.version()
+    }
+
+    /// Get the byte offset where the search resumes
+    #[wasm_bindgen(getter)]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Document, SearchOptions};
+
+    fn document() -> Document {
+        let mut doc = Document::new(Some("foo bar\n".repeat(200)));
+        doc.insert(0, "Foo ");
+        doc
+    }
+
+    #[test]
+    fn test_one_at_a_time_matches_find_all() {
+        let doc = document();
+        let mut cursor = doc.search_cursor_with("foo", SearchOptions::default());
+        let ranges: Vec<_> = std::iter::from_fn(|| cursor.next_range(&doc)).collect();
+        assert_eq!(ranges.len(), 201);
+        assert_eq!(ranges, doc.find_all_ranges("foo", SearchOptions::default()));
+        assert_eq!(cursor.next_range(&doc), None);
+    }
+
+    #[test]
+    fn test_batches_match_find_all() {
+        let doc = document();
+        let options = SearchOptions { case_sensitive: true, whole_word: true };
+        let mut cursor = doc.search_cursor_with("foo", options);
+        let mut ranges = Vec::new();
+        loop {
+            let batch = cursor.next_ranges(&doc, 32);
+            let done = batch.len() < 32;
+            ranges.extend(batch);
+            if done {
+                break;
+            }
+        }
+        assert_eq!(ranges.len(), 200);
+        assert_eq!(ranges, doc.find_all_ranges("foo", options));
+    }
+
+    #[test]
+    fn test_edit_invalidates_search_cursor() {
+        let mut doc = document();
+        let mut cursor = doc.search_cursor_with("bar", SearchOptions::default());
+        assert!(cursor.next_range(&doc).is_some());
+
+        doc.insert(0, "bar ");
+        assert!(!cursor.is_valid(&doc));
+        assert_eq!(cursor.next_range(&doc), None);
+        assert!(cursor.next_ranges(&doc, 10).is_empty());
+    }
+}
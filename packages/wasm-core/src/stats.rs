@@ -0,0 +1,129 @@
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Counts describing a selected range of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct SelectionStats {
+    /// Number of Unicode scalar values
+    pub chars: usize,
+    /// Number of extended grapheme clusters
+    pub graphemes: usize,
+    /// Number of runs of non-whitespace characters
+    pub words: usize,
+    /// Number of lines touched by the selection (0 when empty)
+    pub lines: usize,
+    /// Length in UTF-8 bytes
+    pub bytes: usize,
+}
+
+impl SelectionStats {
+    /// Compute the statistics for a piece of text
+    pub fn from_text(text: &str) -> Self {
+        let mut stats = SelectionStats {
+            bytes: text.len(),
+            graphemes: text.graphemes(true).count(),
+            ..SelectionStats::default()
+        };
+
+        let mut in_word = false;
+        for c in text.chars() {
+            stats.chars += 1;
+            if c == '\n' {
+                stats.lines += 1;
+            }
+            if c.is_whitespace() {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                stats.words += 1;
+            }
+        }
+
+        if !text.is_empty() {
+            stats.lines += 1;
+        }
+        stats
+    }
+}
+
+/// Counts describing a whole document or a range of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct DocumentStats {
+    /// Length in UTF-8 bytes
+    pub bytes: usize,
+    /// Number of Unicode scalar values
+    pub chars: usize,
+    /// Number of runs of non-whitespace characters
+    pub words: usize,
+    /// Number of lines, counting the empty line after a trailing newline
+    pub lines: usize,
+}
+
+impl DocumentStats {
+    /// Compute the statistics in one pass over text split into chunks
+    /// Words spanning two chunks are counted once
+    pub fn from_chunks<'a>(chunks: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut stats = DocumentStats {
+            lines: 1,
+            ..DocumentStats::default()
+        };
+
+        let mut in_word = false;
+        for chunk in chunks {
+            stats.bytes += chunk.len();
+            for c in chunk.chars() {
+                stats.chars += 1;
+                if c == '\n' {
+                    stats.lines += 1;
+                }
+                if c.is_whitespace() {
+                    in_word = false;
+                } else if !in_word {
+                    in_word = true;
+                    stats.words += 1;
+                }
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_stats_multiline_non_ascii() {
+        let stats = SelectionStats::from_text("caf\u{e9} ole\u{301}\nna\u{ef}ve \u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}");
+        assert_eq!(
+            stats,
+            SelectionStats {
+                chars: 21,
+                graphemes: 16,
+                words: 4,
+                lines: 2,
+                bytes: 37,
+            }
+        );
+    }
+
+    #[test]
+    fn test_selection_stats_empty() {
+        assert_eq!(SelectionStats::from_text(""), SelectionStats::default());
+    }
+
+    #[test]
+    fn test_document_stats_trailing_newline() {
+        let stats = DocumentStats::from_chunks(["first li", "ne\n  second\tline caf\u{e9}\n"]);
+        assert_eq!(
+            stats,
+            DocumentStats {
+                bytes: 31,
+                chars: 30,
+                words: 5,
+                lines: 3,
+            }
+        );
+        assert_eq!(DocumentStats::from_chunks([]), DocumentStats { lines: 1, ..DocumentStats::default() });
+    }
+}
@@ -0,0 +1,114 @@
+use crate::search::is_word_char;
+
+/// Category of a character for word navigation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Word,
+    Whitespace,
+    Punctuation,
+}
+
+impl CharClass {
+    pub fn of(c: char) -> Self {
+        if is_word_char(c) {
+            CharClass::Word
+        } else if c.is_whitespace() {
+            CharClass::Whitespace
+        } else {
+            CharClass::Punctuation
+        }
+    }
+}
+
+/// Find the end of the next word or punctuation run after `offset`, skipping whitespace
+/// `offset` must be a char boundary; returns `text.len()` at the end of the text
+pub fn next_boundary(text: &str, offset: usize) -> usize {
+    let mut chars = text[offset..].char_indices().map(|(i, c)| (offset + i, c)).peekable();
+    while chars.next_if(|&(_, c)| CharClass::of(c) == CharClass::Whitespace).is_some() {}
+
+    let Some(&(_, first)) = chars.peek() else {
+        return text.len();
+    };
+    let class = CharClass::of(first);
+    while chars.next_if(|&(_, c)| CharClass::of(c) == class).is_some() {}
+    chars.peek().map_or(text.len(), |&(i, _)| i)
+}
+
+/// Find the start of the previous word or punctuation run before `offset`, skipping whitespace
+/// `offset` must be a char boundary; returns 0 at the start of the text
+pub fn prev_boundary(text: &str, offset: usize) -> usize {
+    let mut chars = text[..offset].char_indices().rev().peekable();
+    while chars.next_if(|&(_, c)| CharClass::of(c) == CharClass::Whitespace).is_some() {}
+
+    let Some(&(_, last)) = chars.peek() else {
+        return 0;
+    };
+    let class = CharClass::of(last);
+    let mut start = offset;
+    while let Some((i, _)) = chars.next_if(|&(_, c)| CharClass::of(c) == class) {
+        start = i;
+    }
+    start
+}
+
+/// Find the run of same-class characters at `offset`, as for double-click selection
+/// The character after the offset is preferred, unless it is whitespace (or the
+/// end of the text) directly after a word or punctuation run
+pub fn word_range_at(text: &str, offset: usize) -> (usize, usize) {
+    let after = text[offset..].chars().next();
+    let before = text[..offset].chars().next_back();
+    let class = match (before.map(CharClass::of), after.map(CharClass::of)) {
+        (Some(before), None | Some(CharClass::Whitespace)) if before != CharClass::Whitespace => before,
+        (_, Some(after)) => after,
+        (_, None) => return (offset, offset),
+    };
+
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| CharClass::of(c) == class)
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = text[offset..]
+        .char_indices()
+        .find(|&(_, c)| CharClass::of(c) != class)
+        .map_or(text.len(), |(i, _)| offset + i);
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "foo_bar baz.qux";
+
+    #[test]
+    fn test_next_boundary() {
+        assert_eq!(next_boundary(SAMPLE, 0), 7);
+        assert_eq!(next_boundary(SAMPLE, 3), 7);
+        assert_eq!(next_boundary(SAMPLE, 7), 11);
+        assert_eq!(next_boundary(SAMPLE, 11), 12);
+        assert_eq!(next_boundary(SAMPLE, 12), 15);
+        assert_eq!(next_boundary(SAMPLE, 15), 15);
+    }
+
+    #[test]
+    fn test_prev_boundary() {
+        assert_eq!(prev_boundary(SAMPLE, 15), 12);
+        assert_eq!(prev_boundary(SAMPLE, 12), 11);
+        assert_eq!(prev_boundary(SAMPLE, 11), 8);
+        assert_eq!(prev_boundary(SAMPLE, 8), 0);
+        assert_eq!(prev_boundary(SAMPLE, 5), 0);
+        assert_eq!(prev_boundary(SAMPLE, 0), 0);
+    }
+
+    #[test]
+    fn test_word_range_at() {
+        assert_eq!(word_range_at(SAMPLE, 3), (0, 7));
+        assert_eq!(word_range_at(SAMPLE, 7), (0, 7));
+        assert_eq!(word_range_at(SAMPLE, 11), (11, 12));
+        assert_eq!(word_range_at(SAMPLE, 15), (12, 15));
+        assert_eq!(word_range_at("a   b", 2), (1, 4));
+        assert_eq!(word_range_at("", 0), (0, 0));
+    }
+}